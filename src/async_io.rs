@@ -0,0 +1,255 @@
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+use crate::non_empty;
+#[cfg(feature = "hcl")]
+use crate::{hcl_from_slice, HclError};
+#[cfg(feature = "json")]
+use crate::{json_from_slice, JsonError};
+#[cfg(feature = "toml")]
+use crate::{toml_from_slice, TomlParseError, TomlSaveError};
+#[cfg(feature = "yaml")]
+use crate::{yaml_from_slice, YamlError};
+use crate::{ConfigLock, HomeConfig, IoResult, PathError};
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{Error as IoError, ErrorKind};
+
+// Content larger than this is parsed on a blocking thread
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+const BLOCKING_THRESHOLD: usize = 1024 * 1024;
+
+// Blocking work runs on the tokio runtime the future is polled in, otherwise on
+// async-std's thread pool, so both features can be enabled together
+mod rt {
+    use std::io::Result;
+    use std::path::Path;
+
+    pub(crate) async fn spawn_blocking<T, F>(f: F) -> Result<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        #[cfg(feature = "tokio")]
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return tokio::task::spawn_blocking(f)
+                .await
+                .map_err(std::io::Error::other);
+        }
+        #[cfg(feature = "async-std")]
+        let result = Ok(async_std::task::spawn_blocking(f).await);
+        #[cfg(not(feature = "async-std"))]
+        let result = {
+            drop(f);
+            Err(std::io::Error::other("must be called from a tokio runtime"))
+        };
+        result
+    }
+
+    pub(crate) async fn create_dir_all(path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        spawn_blocking(move || std::fs::create_dir_all(path)).await?
+    }
+
+    pub(crate) async fn read(path: &Path) -> Result<Vec<u8>> {
+        let path = path.to_path_buf();
+        spawn_blocking(move || std::fs::read(path)).await?
+    }
+
+    pub(crate) async fn write(path: &Path, data: &[u8]) -> Result<()> {
+        let (path, data) = (path.to_path_buf(), data.to_vec());
+        spawn_blocking(move || std::fs::write(path, data)).await?
+    }
+}
+
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+async fn parse<T, F>(bytes: Vec<u8>, f: F) -> IoResult<T>
+where
+    F: FnOnce(Vec<u8>) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    if bytes.len() < BLOCKING_THRESHOLD {
+        Ok(f(bytes))
+    } else {
+        rt::spawn_blocking(move || f(bytes)).await
+    }
+}
+
+/// Async variants of the IO methods, with the `tokio` or `async-std` feature
+///
+/// With both features, the tokio runtime is used when called from one and async-std's
+/// thread pool otherwise, which also works on `smol` and other executors.
+/// Large contents are parsed on a blocking thread, [`RetryPolicy`](crate::RetryPolicy)
+/// is not applied.
+impl HomeConfig {
+    async fn create_parent_dir_async(&self) -> IoResult<()> {
+        if !self.path.exists() {
+            if let Some(parent) = self.path.parent() {
+                rt::create_dir_all(parent)
+                    .await
+                    .map_err(|err| PathError::wrap(&self.path, err))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Acquire an exclusive lock on the config without blocking the executor
+    ///
+    /// Waits on a blocking thread, see [`lock_exclusive`](Self::lock_exclusive).
+    /// When the future is dropped, eg. by a timeout, a lock acquired afterwards is released.
+    pub async fn lock_exclusive_async(&self) -> IoResult<ConfigLock> {
+        let config = self.clone();
+        rt::spawn_blocking(move || config.lock_exclusive()).await?
+    }
+
+    /// Acquire an exclusive lock on the config, or `None` if it is held elsewhere
+    pub async fn try_lock_exclusive_async(&self) -> IoResult<Option<ConfigLock>> {
+        let config = self.clone();
+        rt::spawn_blocking(move || config.try_lock_exclusive()).await?
+    }
+
+    /// Read the entire contents of a file into a string
+    pub async fn read_to_string_async(&self) -> IoResult<String> {
+        let bytes = self.read_to_vec_async().await?;
+        String::from_utf8(bytes)
+            .map_err(|err| PathError::wrap(&self.path, IoError::new(ErrorKind::InvalidData, err)))
+    }
+
+    /// Read the entire contents of a file into a `Vec<u8>`
+    pub async fn read_to_vec_async(&self) -> IoResult<Vec<u8>> {
+        if self.max_size.is_some() {
+            let size = self.path.metadata();
+            self.check_size(size.map_err(|err| PathError::wrap(&self.path, err))?.len())?;
+        }
+        let bytes = rt::read(&self.path)
+            .await
+            .map_err(|err| PathError::wrap(&self.path, err))?;
+        self.check_size(bytes.len() as u64)?;
+        Ok(bytes)
+    }
+
+    /// Save content to local file
+    pub async fn save_async<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+        self.create_parent_dir_async().await?;
+        rt::write(&self.path, data.as_ref())
+            .await
+            .map_err(|err| PathError::wrap(&self.path, err))
+    }
+
+    /// Parse the config file from `json` content
+    #[cfg(feature = "json")]
+    pub async fn json_async<T>(&self) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let bytes = self.read_to_vec_async().await?;
+        if non_empty(&bytes).is_none() {
+            return Err(JsonError::Empty(self.path.clone()));
+        }
+        let result = parse(bytes, |bytes| json_from_slice(&bytes)).await?;
+        result.map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Parse the config file from `yaml` content
+    #[cfg(feature = "yaml")]
+    pub async fn yaml_async<T>(&self) -> Result<T, YamlError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let bytes = self.read_to_vec_async().await?;
+        if non_empty(&bytes).is_none() {
+            return Err(YamlError::Empty(self.path.clone()));
+        }
+        let result = parse(bytes, |bytes| yaml_from_slice(&bytes)).await?;
+        result.map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Parse the config file from `toml` content
+    #[cfg(feature = "toml")]
+    pub async fn toml_async<T>(&self) -> Result<T, TomlParseError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let bytes = self.read_to_vec_async().await?;
+        if non_empty(&bytes).is_none() {
+            return Err(TomlParseError::Empty(self.path.clone()));
+        }
+        let result = parse(bytes, |bytes| toml_from_slice(&bytes)).await?;
+        result.map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Parse the config file from `hcl` content
+    #[cfg(feature = "hcl")]
+    pub async fn hcl_async<T>(&self) -> Result<T, HclError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let bytes = self.read_to_vec_async().await?;
+        if non_empty(&bytes).is_none() {
+            return Err(HclError::Empty(self.path.clone()));
+        }
+        let result = parse(bytes, |bytes| hcl_from_slice(&bytes)).await?;
+        result.map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Read, change and save the `json` config while holding the exclusive lock,
+    /// see [`modify_json`](Self::modify_json)
+    ///
+    /// The lock is acquired with [`lock_exclusive_async`](Self::lock_exclusive_async)
+    /// and the file is replaced atomically on a blocking thread.
+    #[cfg(feature = "json")]
+    pub async fn modify_json_async<T, R, F>(&self, f: F) -> Result<R, JsonError>
+    where
+        T: Serialize + DeserializeOwned + Default + Send + 'static,
+        F: FnOnce(&mut T) -> R,
+    {
+        let _lock = self.lock_exclusive_async().await?;
+        let mut data = match self.json_async::<T>().await {
+            Ok(data) => data,
+            Err(err) if err.is_not_found() || err.is_empty_file() => T::default(),
+            Err(err) => return Err(err),
+        };
+        let result = f(&mut data);
+        let config = self.clone();
+        rt::spawn_blocking(move || config.save_json_streaming(data)).await??;
+        Ok(result)
+    }
+
+    /// Save struct to local file (`json` format)
+    #[cfg(feature = "json")]
+    pub async fn save_json_async<T>(&self, data: T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_json_string(&data).map_err(self.serde_error())?;
+        self.save_async(bytes).await.map_err(JsonError::Io)
+    }
+
+    /// Save struct to local file (`yaml` format)
+    #[cfg(feature = "yaml")]
+    pub async fn save_yaml_async<T>(&self, data: T) -> Result<(), YamlError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_yaml_string(&data).map_err(self.serde_error())?;
+        self.save_async(bytes).await.map_err(YamlError::Io)
+    }
+
+    /// Save struct to local file (`toml` format)
+    #[cfg(feature = "toml")]
+    pub async fn save_toml_async<T>(&self, data: T) -> Result<(), TomlSaveError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_toml_string(&data).map_err(self.serde_error())?;
+        self.save_async(bytes).await.map_err(TomlSaveError::Io)
+    }
+
+    /// Save struct to local file (`hcl` format)
+    #[cfg(feature = "hcl")]
+    pub async fn save_hcl_async<T>(&self, data: T) -> Result<(), HclError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_hcl_string(&data).map_err(self.serde_error())?;
+        self.save_async(bytes).await.map_err(HclError::Io)
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{ConfigFile, ConfigFileError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+type ErrorCallback = Box<dyn FnMut(ConfigFileError) + Send>;
+
+struct Shared<T> {
+    file: Mutex<(ConfigFile<T>, bool)>,
+    on_error: Mutex<Option<ErrorCallback>>,
+}
+
+impl<T> Shared<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn save_if_dirty(&self) -> Result<(), ConfigFileError> {
+        let mut guard = self.file.lock().unwrap();
+        let (file, dirty) = &mut *guard;
+        if *dirty {
+            file.save_force()?;
+            *dirty = false;
+        }
+        Ok(())
+    }
+
+    fn report(&self, result: Result<(), ConfigFileError>) {
+        if let Err(err) = result {
+            if let Some(callback) = self.on_error.lock().unwrap().as_mut() {
+                callback(err);
+            }
+        }
+    }
+}
+
+enum Worker {
+    Thread(Sender<()>, JoinHandle<()>),
+    #[cfg(feature = "tokio")]
+    Task(tokio::sync::oneshot::Sender<()>),
+}
+
+/// A [`ConfigFile`] saved in the background, created by [`ConfigFile::spawn_autosave`]
+/// or, with the `tokio` feature, `ConfigFile::spawn_autosave_task`
+///
+/// Changes made with [`modify`](Self::modify) are saved at most once per interval,
+/// each save replaces the file atomically. When dropped, pending changes are saved
+/// before the background thread or task stops.
+///
+/// ```no_run
+/// use home_config::{ConfigFile, HomeConfig};
+/// use std::time::Duration;
+///
+/// let config = ConfigFile::<serde_json::Value>::load(HomeConfig::with_file("test.json")).unwrap();
+/// let settings = config.spawn_autosave_with_on_error(Duration::from_secs(5), |err| {
+///     eprintln!("Save failed: {:?}", err);
+/// });
+///
+/// settings.modify(|value| value["name"] = "XiaoMing".into());
+/// ```
+pub struct AutosaveHandle<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    shared: Arc<Shared<T>>,
+    worker: Option<Worker>,
+}
+
+impl<T> ConfigFile<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    /// Save the value on a background thread every `interval` while it has unsaved changes
+    ///
+    /// Saves overwrite changes made to the file by someone else, see
+    /// [`save_force`](Self::save_force). The errors of saves are dropped until
+    /// [`on_error`](AutosaveHandle::on_error) is called, use
+    /// [`spawn_autosave_with_on_error`](Self::spawn_autosave_with_on_error) to receive
+    /// all of them.
+    pub fn spawn_autosave(self, interval: Duration) -> AutosaveHandle<T> {
+        self.start_autosave(interval, None)
+    }
+
+    /// Like [`spawn_autosave`](Self::spawn_autosave), calling `on_error` with the errors
+    /// of every failed save
+    pub fn spawn_autosave_with_on_error<F>(
+        self,
+        interval: Duration,
+        on_error: F,
+    ) -> AutosaveHandle<T>
+    where
+        F: FnMut(ConfigFileError) + Send + 'static,
+    {
+        self.start_autosave(interval, Some(Box::new(on_error)))
+    }
+
+    fn start_autosave(
+        self,
+        interval: Duration,
+        on_error: Option<ErrorCallback>,
+    ) -> AutosaveHandle<T> {
+        let shared = Arc::new(Shared {
+            file: Mutex::new((self, false)),
+            on_error: Mutex::new(on_error),
+        });
+        let (stop, rx) = mpsc::channel();
+
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || loop {
+                match rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => shared.report(shared.save_if_dirty()),
+                    _ => return shared.report(shared.save_if_dirty()),
+                }
+            })
+        };
+
+        AutosaveHandle {
+            shared,
+            worker: Some(Worker::Thread(stop, thread)),
+        }
+    }
+
+    /// [`spawn_autosave`](Self::spawn_autosave) on a tokio task instead of a thread
+    ///
+    /// Saves run on the blocking thread pool. The final save on drop blocks the
+    /// dropping thread. Panics outside a tokio runtime, like `tokio::spawn`.
+    #[cfg(feature = "tokio")]
+    pub fn spawn_autosave_task(self, interval: Duration) -> AutosaveHandle<T> {
+        self.start_autosave_task(interval, None)
+    }
+
+    /// Like [`spawn_autosave_task`](Self::spawn_autosave_task), calling `on_error` with
+    /// the errors of every failed save
+    #[cfg(feature = "tokio")]
+    pub fn spawn_autosave_task_with_on_error<F>(
+        self,
+        interval: Duration,
+        on_error: F,
+    ) -> AutosaveHandle<T>
+    where
+        F: FnMut(ConfigFileError) + Send + 'static,
+    {
+        self.start_autosave_task(interval, Some(Box::new(on_error)))
+    }
+
+    #[cfg(feature = "tokio")]
+    fn start_autosave_task(
+        self,
+        interval: Duration,
+        on_error: Option<ErrorCallback>,
+    ) -> AutosaveHandle<T> {
+        let shared = Arc::new(Shared {
+            file: Mutex::new((self, false)),
+            on_error: Mutex::new(on_error),
+        });
+        let (stop, mut rx) = tokio::sync::oneshot::channel::<()>();
+
+        {
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                while tokio::time::timeout(interval, &mut rx).await.is_err() {
+                    let shared = shared.clone();
+                    let save = move || shared.report(shared.save_if_dirty());
+                    let _ = tokio::task::spawn_blocking(save).await;
+                }
+            });
+        }
+
+        AutosaveHandle {
+            shared,
+            worker: Some(Worker::Task(stop)),
+        }
+    }
+}
+
+impl<T> AutosaveHandle<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Change the value and mark it as unsaved
+    pub fn modify<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut guard = self.shared.file.lock().unwrap();
+        guard.1 = true;
+        f(guard.0.get_mut())
+    }
+
+    /// Read the value
+    pub fn read<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(self.shared.file.lock().unwrap().0.get())
+    }
+
+    /// Save unsaved changes now instead of waiting for the next interval
+    pub fn flush_now(&self) -> Result<(), ConfigFileError> {
+        self.shared.save_if_dirty()
+    }
+
+    /// Call `callback` with the errors of background saves from now on
+    pub fn on_error<F>(&self, callback: F)
+    where
+        F: FnMut(ConfigFileError) + Send + 'static,
+    {
+        *self.shared.on_error.lock().unwrap() = Some(Box::new(callback));
+    }
+}
+
+impl<T> Drop for AutosaveHandle<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn drop(&mut self) {
+        match self.worker.take() {
+            Some(Worker::Thread(stop, thread)) => {
+                // Wakes the thread for a final save
+                drop(stop);
+                let _ = thread.join();
+            }
+            #[cfg(feature = "tokio")]
+            Some(Worker::Task(stop)) => {
+                drop(stop);
+                self.shared.report(self.shared.save_if_dirty());
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::error::{io_kind, json_kind};
+use crate::{
+    deserialize, display_path, non_empty, ConfigErrorKind, HomeConfig, JsonError, SerdeError,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{Error as IoError, ErrorKind};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// [`ConfigFile`] error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigFileError {
+    Io(IoError),
+    Serde(SerdeError<serde_json::Error>),
+    /// The file was changed by someone else since it was loaded or saved
+    Conflict {
+        theirs_changed_at: Option<SystemTime>,
+    },
+    /// The config file is empty or only whitespace
+    Empty(PathBuf),
+}
+
+impl From<JsonError> for ConfigFileError {
+    fn from(err: JsonError) -> Self {
+        match err {
+            JsonError::Io(err) => Self::Io(err),
+            JsonError::Serde(err) => Self::Serde(err),
+            JsonError::Empty(path) => Self::Empty(path),
+        }
+    }
+}
+
+impl ConfigFileError {
+    /// Get the category of the error
+    pub fn kind(&self) -> ConfigErrorKind {
+        match self {
+            Self::Io(err) => io_kind(err),
+            Self::Serde(err) => json_kind(err),
+            Self::Conflict { .. } => ConfigErrorKind::Conflict,
+            Self::Empty(_) => ConfigErrorKind::Empty,
+        }
+    }
+
+    kind_predicates!();
+
+    /// The path of the field a parse error happened in, eg. `servers[2].port`,
+    /// see [`SerdeError::field_path`]
+    pub fn field_path(&self) -> Option<&str> {
+        match self {
+            Self::Serde(err) => err.field_path(),
+            _ => None,
+        }
+    }
+
+    /// The path of the config file the error happened in, if known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Io(err) => crate::PathError::of(err).map(crate::PathError::path),
+            Self::Serde(err) => err.path(),
+            Self::Empty(path) => Some(path),
+            Self::Conflict { .. } => None,
+        }
+    }
+
+    /// Whether the file was changed by someone else
+    pub fn is_conflict(&self) -> bool {
+        self.kind() == ConfigErrorKind::Conflict
+    }
+}
+
+impl From<IoError> for ConfigFileError {
+    fn from(err: IoError) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ConfigFileError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Serde(err.into())
+    }
+}
+
+impl From<SerdeError<serde_json::Error>> for ConfigFileError {
+    fn from(err: SerdeError<serde_json::Error>) -> Self {
+        Self::Serde(err)
+    }
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::Serde(err) => write!(f, "json error: {}", err),
+            Self::Conflict { .. } => write!(f, "config file was changed by someone else"),
+            Self::Empty(path) => write!(f, "{}: config file is empty", display_path(path)),
+        }
+    }
+}
+
+impl Error for ConfigFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Serde(err) => Some(err.get_ref()),
+            Self::Conflict { .. } | Self::Empty(_) => None,
+        }
+    }
+}
+
+/// How [`ConfigFile::save_merged`] resolves a key changed on both sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the in-memory value
+    Ours,
+    /// Keep the value on disk
+    Theirs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl Stamp {
+    fn read(config: &HomeConfig) -> Result<Option<(Self, Vec<u8>)>, IoError> {
+        let bytes = match config.read_to_vec() {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let modified = config.path.metadata()?.modified().ok();
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let stamp = Self {
+            modified,
+            hash: hasher.finish(),
+        };
+        Ok(Some((stamp, bytes)))
+    }
+}
+
+/// A `json` config value that remembers the file version it was loaded from
+///
+/// [`save_checked`](Self::save_checked) refuses to overwrite changes made to the file
+/// by someone else, e.g. the user editing it while the app is running.
+/// All saves hold [`HomeConfig::lock_exclusive`] while comparing and writing, and replace
+/// the file atomically, see [`HomeConfig::save_json_streaming`].
+///
+/// ```no_run
+/// use home_config::{ConfigFile, ConfigFileError, HomeConfig};
+///
+/// let mut config = ConfigFile::<serde_json::Value>::load(HomeConfig::with_file("test.json")).unwrap();
+/// config["name"] = "XiaoMing".into();
+/// match config.save_checked() {
+///     Err(ConfigFileError::Conflict { .. }) => println!("Changed on disk, not saved"),
+///     other => other.unwrap(),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ConfigFile<T> {
+    config: HomeConfig,
+    value: T,
+    base: Option<Value>,
+    stamp: Option<Stamp>,
+}
+
+impl<T> ConfigFile<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Parse the config file and remember its version
+    pub fn load(config: HomeConfig) -> Result<Self, ConfigFileError> {
+        let (stamp, bytes) = Stamp::read(&config)?
+            .ok_or_else(|| ConfigFileError::Io(IoError::from(ErrorKind::NotFound)))?;
+        let bytes = non_empty(&bytes).ok_or_else(|| ConfigFileError::Empty(config.path.clone()))?;
+        let base = serde_json::from_slice::<Value>(bytes)
+            .map_err(|err| SerdeError::from(err).in_file(&config.path))?;
+        let value = deserialize(&base).map_err(|err| err.in_file(&config.path))?;
+        Ok(Self {
+            config,
+            value,
+            base: Some(base),
+            stamp: Some(stamp),
+        })
+    }
+
+    /// Wrap a value for a config file that does not exist yet
+    pub fn new(config: HomeConfig, value: T) -> Self {
+        Self {
+            config,
+            value,
+            base: None,
+            stamp: None,
+        }
+    }
+
+    /// Get the underlying config
+    pub fn config(&self) -> &HomeConfig {
+        &self.config
+    }
+
+    /// Get the value
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Get the value mutably
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Unwrap the value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    fn write(&mut self, value: Value) -> Result<(), ConfigFileError> {
+        self.config.save_json_streaming(&value)?;
+        self.stamp = Stamp::read(&self.config)?.map(|(stamp, _)| stamp);
+        self.base = Some(value);
+        Ok(())
+    }
+
+    /// Save the value, unless the file changed since it was loaded or last saved
+    pub fn save_checked(&mut self) -> Result<(), ConfigFileError> {
+        let _lock = self.config.lock_exclusive()?;
+        let current = Stamp::read(&self.config)?;
+        if current.as_ref().map(|(stamp, _)| stamp) != self.stamp.as_ref() {
+            return Err(ConfigFileError::Conflict {
+                theirs_changed_at: current.and_then(|(stamp, _)| stamp.modified),
+            });
+        }
+        let value = serde_json::to_value(&self.value)?;
+        self.write(value)
+    }
+
+    /// Save the value, overwriting any change made to the file
+    pub fn save_force(&mut self) -> Result<(), ConfigFileError> {
+        let _lock = self.config.lock_exclusive()?;
+        let value = serde_json::to_value(&self.value)?;
+        self.write(value)
+    }
+
+    /// Save the value, three-way merging it with changes made to the file
+    ///
+    /// Keys changed only on one side keep that change; keys changed on both sides
+    /// are resolved by `strategy`. The merged value replaces the in-memory value.
+    pub fn save_merged(&mut self, strategy: MergeStrategy) -> Result<(), ConfigFileError> {
+        let _lock = self.config.lock_exclusive()?;
+        let ours = serde_json::to_value(&self.value)?;
+        let merged = match Stamp::read(&self.config)? {
+            Some((stamp, _)) if Some(&stamp) == self.stamp.as_ref() => ours,
+            Some((_, bytes)) => {
+                let theirs = serde_json::from_slice(&bytes)?;
+                merge(self.base.as_ref(), Some(ours), Some(theirs), strategy).unwrap_or(Value::Null)
+            }
+            None => ours,
+        };
+        self.value = deserialize(&merged).map_err(|err| err.in_file(&self.config.path))?;
+        self.write(merged)
+    }
+}
+
+impl<T> Deref for ConfigFile<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for ConfigFile<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+fn merge(
+    base: Option<&Value>,
+    ours: Option<Value>,
+    theirs: Option<Value>,
+    strategy: MergeStrategy,
+) -> Option<Value> {
+    if ours == theirs || base == theirs.as_ref() {
+        return ours;
+    }
+    if base == ours.as_ref() {
+        return theirs;
+    }
+    match (ours, theirs) {
+        (Some(Value::Object(mut ours)), Some(Value::Object(mut theirs))) => {
+            let base = base.and_then(Value::as_object);
+            let mut keys = ours.keys().cloned().collect::<Vec<_>>();
+            keys.extend(theirs.keys().filter(|k| !ours.contains_key(*k)).cloned());
+            let mut merged = Map::new();
+            for key in keys {
+                let value = merge(
+                    base.and_then(|base| base.get(&key)),
+                    ours.remove(&key),
+                    theirs.remove(&key),
+                    strategy,
+                );
+                if let Some(value) = value {
+                    merged.insert(key, value);
+                }
+            }
+            Some(Value::Object(merged))
+        }
+        (ours, theirs) => match strategy {
+            MergeStrategy::Ours => ours,
+            MergeStrategy::Theirs => theirs,
+        },
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{display_path, non_empty, Error, Format, HomeConfig};
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode};
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+/// A config error with the file content, rendered by [`miette`] as an annotated snippet
+///
+/// Created by [`HomeConfig::parse_diagnostic`], with the `diagnostics` feature.
+#[derive(Debug)]
+pub struct ConfigDiagnostic {
+    error: Box<Error>,
+    source: Option<Box<NamedSource<String>>>,
+    offset: Option<usize>,
+}
+
+impl ConfigDiagnostic {
+    /// Get the underlying error
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Unwrap the underlying error
+    pub fn into_error(self) -> Error {
+        *self.error
+    }
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+// No `source`, the message already includes it and reports would repeat it
+impl std::error::Error for ConfigDiagnostic {}
+
+impl Diagnostic for ConfigDiagnostic {
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = match self.source.as_ref()?.name() {
+            name if self.error.is_parse_error() => format!("fix the config file `{}`", name),
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&**self.source.as_ref()?)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let span = LabeledSpan::at_offset(self.offset?, "here");
+        Some(Box::new(std::iter::once(span)))
+    }
+}
+
+// Byte offset of a one-based line and column, counted in bytes or in chars
+fn offset(content: &str, (line, column): (usize, usize), byte_columns: bool) -> usize {
+    let start = content
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    let rest = &content[start.min(content.len())..];
+    let column = column.saturating_sub(1);
+    let column = if byte_columns {
+        let mut column = column.min(rest.len());
+        while !rest.is_char_boundary(column) {
+            column -= 1;
+        }
+        column
+    } else {
+        rest.char_indices()
+            .nth(column)
+            .map_or(rest.len(), |(i, _)| i)
+    };
+    start + column
+}
+
+impl HomeConfig {
+    /// Parse the config file in `format`, keeping the content for a [`miette`] report on errors
+    ///
+    /// ```no_run
+    /// use home_config::{Format, HomeConfig};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Settings {
+    ///     name: String,
+    /// }
+    ///
+    /// fn load(format: Format) -> miette::Result<Settings> {
+    ///     let config = HomeConfig::with_config_dir("app", "config");
+    ///     Ok(config.parse_diagnostic(format)?)
+    /// }
+    /// ```
+    pub fn parse_diagnostic<T>(&self, format: Format) -> Result<T, ConfigDiagnostic>
+    where
+        T: DeserializeOwned,
+    {
+        let content = self.read_to_string().map_err(|err| ConfigDiagnostic {
+            error: Box::new(Error::from(err)),
+            source: None,
+            offset: None,
+        })?;
+        if non_empty(content.as_bytes()).is_none() {
+            return Err(ConfigDiagnostic {
+                error: Box::new(Error::Empty(self.path.clone())),
+                source: None,
+                offset: None,
+            });
+        }
+        let bytes = content.as_bytes();
+        // The path is shown with the source, not in the message. `serde_json` counts
+        // columns in bytes, the others in chars
+        let (result, byte_columns) = match format {
+            #[cfg(feature = "json")]
+            Format::Json => (crate::json_from_slice(bytes).map_err(Error::from), true),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => (crate::yaml_from_slice(bytes).map_err(Error::from), false),
+            #[cfg(feature = "toml")]
+            Format::Toml => (crate::toml_from_slice(bytes).map_err(Error::from), false),
+            #[cfg(feature = "hcl")]
+            Format::Hcl => (crate::hcl_from_slice(bytes).map_err(Error::from), false),
+        };
+        result.map_err(|error| ConfigDiagnostic {
+            offset: match error.span() {
+                Some(span) => Some(span.start),
+                None => error
+                    .location()
+                    .map(|location| offset(&content, location, byte_columns)),
+            },
+            source: Some(Box::new(NamedSource::new(
+                display_path(&self.path),
+                content,
+            ))),
+            error: Box::new(error),
+        })
+    }
+}
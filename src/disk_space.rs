@@ -0,0 +1,89 @@
+use crate::{HomeConfig, IoResult, PathError};
+use std::io::{Error as IoError, ErrorKind};
+use std::path::Path;
+
+#[cfg(unix)]
+fn available(path: &Path) -> IoResult<u64> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is nul terminated and `stat` is only read after `statvfs` filled it
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(IoError::last_os_error());
+        }
+        stat.assume_init()
+    };
+    // The field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn available(path: &Path) -> IoResult<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<_>>();
+    let mut available = 0;
+    // SAFETY: `path` is nul terminated, the totals are optional
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    match ok {
+        0 => Err(IoError::last_os_error()),
+        _ => Ok(available),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available(_: &Path) -> IoResult<u64> {
+    Err(IoError::new(
+        ErrorKind::Unsupported,
+        "disk space is not available on this platform",
+    ))
+}
+
+impl HomeConfig {
+    /// Get the space available to the current user, in bytes, on the file system of the config file
+    ///
+    /// The file and its directory do not have to exist, the closest existing parent directory is used.
+    pub fn available_disk_space(&self) -> IoResult<u64> {
+        let mut path = self.path.as_path();
+        while !path.exists() {
+            path = match path.parent() {
+                Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+        available(path).map_err(|err| PathError::wrap(path, err))
+    }
+
+    /// Fail with `StorageFull` if less than `bytes` are available, eg. before writing a large config
+    ///
+    /// The space can still run out while writing, if something else uses it in the meantime.
+    pub fn assert_space_for(&self, bytes: u64) -> IoResult<()> {
+        let available = self.available_disk_space()?;
+        if available < bytes {
+            let msg = format!("{} bytes needed, only {} bytes available", bytes, available);
+            return Err(PathError::wrap(
+                &self.path,
+                IoError::new(ErrorKind::StorageFull, msg),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+use std::env;
+
+/// Replace `$NAME` and `${NAME}` with the value of the environment variable `NAME`
+///
+/// Unset variables are kept as written and `$$` is a literal `$`.
+/// Only allocates when something was replaced, otherwise `s` is borrowed.
+///
+/// ```
+/// use home_config::expand_env_cow;
+/// use std::borrow::Cow;
+///
+/// assert!(matches!(expand_env_cow("no variables"), Cow::Borrowed(_)));
+/// ```
+pub fn expand_env_cow(s: &str) -> Cow<'_, str> {
+    let mut out = String::new();
+    // End of the part of `s` already handled
+    let mut copied = 0;
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        let start = s.len() - rest.len() + i;
+        let after = &rest[i + 1..];
+        let (name, len) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else if let Some(after) = after.strip_prefix('$') {
+            out.push_str(&s[copied..start]);
+            out.push('$');
+            copied = start + 2;
+            rest = after;
+            continue;
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                out.push_str(&s[copied..start]);
+                out.push_str(&value);
+                copied = start + 1 + len;
+            }
+            _ => {}
+        }
+        rest = &s[start + 1 + len..];
+    }
+    if copied == 0 {
+        return Cow::Borrowed(s);
+    }
+    out.push_str(&s[copied..]);
+    Cow::Owned(out)
+}
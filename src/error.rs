@@ -0,0 +1,549 @@
+use crate::display_path;
+#[cfg(feature = "hcl")]
+use crate::HclError;
+#[cfg(feature = "json")]
+use crate::JsonError;
+#[cfg(feature = "yaml")]
+use crate::YamlError;
+#[cfg(feature = "toml")]
+use crate::{TomlParseError, TomlSaveError};
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind};
+use std::ops::{Deref, Range};
+use std::path::{Path, PathBuf};
+
+// `is_*` predicates on top of a `kind` method
+macro_rules! kind_predicates {
+    () => {
+        /// Whether the config file does not exist
+        pub fn is_not_found(&self) -> bool {
+            self.kind() == ConfigErrorKind::NotFound
+        }
+
+        /// Whether the config file cannot be accessed
+        pub fn is_permission_denied(&self) -> bool {
+            self.kind() == ConfigErrorKind::PermissionDenied
+        }
+
+        /// Whether the content is malformed or does not match the expected type
+        pub fn is_parse_error(&self) -> bool {
+            matches!(self.kind(), ConfigErrorKind::Syntax | ConfigErrorKind::Data)
+        }
+
+        /// Whether the config file is empty or only whitespace, eg. to fall back to defaults
+        pub fn is_empty_file(&self) -> bool {
+            self.kind() == ConfigErrorKind::Empty
+        }
+    };
+}
+
+/// The category of an error, eg. [`Error::kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigErrorKind {
+    /// The config file does not exist
+    NotFound,
+    /// The config file cannot be accessed
+    PermissionDenied,
+    /// Any other IO error
+    Io,
+    /// The content is malformed, every parse error of formats other than `json`
+    Syntax,
+    /// The content does not match the expected type
+    Data,
+    /// The value cannot be represented in the format
+    Serialize,
+    /// The config file was changed by someone else
+    Conflict,
+    /// The config file is empty or only whitespace, not a parse error
+    Empty,
+    /// The config file is larger than the limit, see [`TooLargeError`]
+    TooLarge,
+}
+
+pub(crate) fn io_kind(err: &IoError) -> ConfigErrorKind {
+    if TooLargeError::of(err).is_some() {
+        return ConfigErrorKind::TooLarge;
+    }
+    match err.kind() {
+        ErrorKind::NotFound => ConfigErrorKind::NotFound,
+        ErrorKind::PermissionDenied => ConfigErrorKind::PermissionDenied,
+        _ => ConfigErrorKind::Io,
+    }
+}
+
+#[cfg(feature = "json")]
+pub(crate) fn json_kind(err: &serde_json::Error) -> ConfigErrorKind {
+    match err.classify() {
+        serde_json::error::Category::Io => ConfigErrorKind::Io,
+        serde_json::error::Category::Syntax | serde_json::error::Category::Eof => {
+            ConfigErrorKind::Syntax
+        }
+        serde_json::error::Category::Data => ConfigErrorKind::Data,
+    }
+}
+
+/// The path of a config file an IO error happened on
+///
+/// IO errors returned by [`HomeConfig`](crate::HomeConfig) wrap it, keeping their
+/// [`kind`](IoError::kind), and are displayed like `~/.config/app/config: No such file or directory`.
+///
+/// ```no_run
+/// use home_config::{HomeConfig, PathError};
+///
+/// let err = HomeConfig::with_file("missing.json").read_to_string().unwrap_err();
+/// let path = PathError::of(&err).unwrap().path();
+/// ```
+#[derive(Debug)]
+pub struct PathError {
+    path: PathBuf,
+    source: IoError,
+}
+
+impl PathError {
+    pub(crate) fn wrap(path: &Path, err: IoError) -> IoError {
+        if Self::of(&err).is_some() {
+            return err;
+        }
+        let source = Self {
+            path: path.to_path_buf(),
+            source: err,
+        };
+        IoError::new(source.source.kind(), source)
+    }
+
+    /// Get the path error wrapped in `err`, if any
+    pub fn of(err: &IoError) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    /// Get the config file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", display_path(&self.path), self.source)
+    }
+}
+
+impl std::error::Error for PathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// A serde error, with the config file and the field it happened in
+///
+/// The `Serde` variants of the format errors wrap it. It dereferences to the serde error,
+/// eg. for the line of a `json` error, and is displayed like
+/// `~/.config/app/config.toml: servers[2].port: invalid type`.
+#[derive(Debug)]
+pub struct SerdeError<E> {
+    error: E,
+    // Boxed to keep the format errors small
+    context: Box<Context>,
+}
+
+#[derive(Debug, Default)]
+struct Context {
+    path: Option<PathBuf>,
+    field: Option<String>,
+}
+
+impl<E> SerdeError<E> {
+    // An error outside of any known field, eg. at the root, has no field path
+    #[cfg(any(
+        feature = "lenient",
+        all(
+            feature = "field-path",
+            any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl")
+        )
+    ))]
+    pub(crate) fn with_field(err: serde_path_to_error::Error<E>) -> Self {
+        use serde_path_to_error::Segment;
+
+        let known = err.path().iter().any(|s| !matches!(s, Segment::Unknown));
+        let field = match cfg!(feature = "field-path") && known {
+            true => Some(err.path().to_string()),
+            false => None,
+        };
+        Self {
+            error: err.into_inner(),
+            context: Box::new(Context { path: None, field }),
+        }
+    }
+
+    #[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+    pub(crate) fn in_file(mut self, path: &Path) -> Self {
+        self.context.path.get_or_insert_with(|| path.to_path_buf());
+        self
+    }
+
+    /// Get the config file path, `None` for content that was not read from a file
+    pub fn path(&self) -> Option<&Path> {
+        self.context.path.as_deref()
+    }
+
+    /// The path of the field the error happened in, eg. `servers[2].port`
+    ///
+    /// Only recorded with the `field-path` feature, `None` otherwise and for errors
+    /// outside of any field, eg. at the start of the file.
+    pub fn field_path(&self) -> Option<&str> {
+        self.context.field.as_deref()
+    }
+
+    /// Get the serde error
+    pub fn get_ref(&self) -> &E {
+        &self.error
+    }
+
+    /// Unwrap the serde error
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+
+    // Written before the message, which some formats render themselves
+    pub(crate) fn fmt_prefix(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.context.path {
+            write!(f, "{}: ", display_path(path))?;
+        }
+        if let Some(field) = &self.context.field {
+            write!(f, "{}: ", field)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E> From<E> for SerdeError<E> {
+    fn from(error: E) -> Self {
+        Self {
+            error,
+            context: Box::default(),
+        }
+    }
+}
+
+impl<E> Deref for SerdeError<E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        &self.error
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for SerdeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_prefix(f)?;
+        self.error.fmt(f)
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for SerdeError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// A config file larger than the limit set by [`HomeConfig::with_max_size`](crate::HomeConfig::with_max_size)
+///
+/// IO errors wrap it with kind `InvalidData`.
+///
+/// ```no_run
+/// use home_config::{HomeConfig, TooLargeError};
+///
+/// let config = HomeConfig::with_file("large.json").with_max_size(1024);
+/// let err = config.read_to_vec().unwrap_err();
+/// let size = TooLargeError::of(&err).unwrap().size();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLargeError {
+    pub(crate) size: u64,
+    pub(crate) limit: u64,
+}
+
+impl TooLargeError {
+    /// Get the size limit error wrapped in `err`, if any
+    pub fn of(err: &IoError) -> Option<&Self> {
+        let inner = err.get_ref()?;
+        match inner.downcast_ref::<PathError>() {
+            Some(err) => Self::of(&err.source),
+            None => inner.downcast_ref(),
+        }
+    }
+
+    /// Get the size of the file in bytes, or how much was read before stopping
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the limit in bytes
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl fmt::Display for TooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "file is larger than the limit of {} bytes: {} bytes",
+            self.limit, self.size
+        )
+    }
+}
+
+impl std::error::Error for TooLargeError {}
+
+/// [`HomeConfig::with_runtime_dir`](crate::HomeConfig::with_runtime_dir) error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RuntimeDirError {
+    /// `XDG_RUNTIME_DIR` is not set or not an absolute path
+    NotSet,
+    /// The app name is not a single path segment, with kind `InvalidInput`
+    Io(IoError),
+}
+
+impl fmt::Display for RuntimeDirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSet => f.write_str("XDG_RUNTIME_DIR is not set"),
+            Self::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeDirError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NotSet => None,
+            Self::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<IoError> for RuntimeDirError {
+    fn from(err: IoError) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// `NotSet` is `NotFound`, so `?` works in functions returning an IO result
+impl From<RuntimeDirError> for IoError {
+    fn from(err: RuntimeDirError) -> Self {
+        match err {
+            RuntimeDirError::NotSet => IoError::new(ErrorKind::NotFound, err),
+            RuntimeDirError::Io(err) => err,
+        }
+    }
+}
+
+/// An error of any format
+///
+/// Every format error converts into it, so `?` works in code handling several formats.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    Io(IoError),
+    #[cfg(feature = "json")]
+    Json(SerdeError<serde_json::Error>),
+    #[cfg(feature = "yaml")]
+    Yaml(SerdeError<serde_yaml::Error>),
+    #[cfg(feature = "toml")]
+    TomlDe(SerdeError<toml::de::Error>),
+    #[cfg(feature = "toml")]
+    TomlSer(SerdeError<toml::ser::Error>),
+    #[cfg(feature = "hcl")]
+    Hcl(SerdeError<hcl::Error>),
+    /// The config file is empty or only whitespace
+    Empty(PathBuf),
+}
+
+impl Error {
+    /// Get the category of the error
+    pub fn kind(&self) -> ConfigErrorKind {
+        match self {
+            Self::Io(err) => io_kind(err),
+            Self::Empty(_) => ConfigErrorKind::Empty,
+            #[cfg(feature = "json")]
+            Self::Json(err) => json_kind(err),
+            #[cfg(feature = "toml")]
+            Self::TomlSer(_) => ConfigErrorKind::Serialize,
+            #[cfg(any(feature = "yaml", feature = "toml", feature = "hcl"))]
+            _ => ConfigErrorKind::Syntax,
+        }
+    }
+
+    kind_predicates!();
+
+    /// Get the config file path, see [`SerdeError::path`] and [`PathError`]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Io(err) => PathError::of(err).map(PathError::path),
+            Self::Empty(path) => Some(path),
+            #[cfg(feature = "json")]
+            Self::Json(err) => err.path(),
+            #[cfg(feature = "yaml")]
+            Self::Yaml(err) => err.path(),
+            #[cfg(feature = "toml")]
+            Self::TomlDe(err) => err.path(),
+            #[cfg(feature = "toml")]
+            Self::TomlSer(err) => err.path(),
+            #[cfg(feature = "hcl")]
+            Self::Hcl(err) => err.path(),
+        }
+    }
+
+    /// The path of the field a parse error happened in, eg. `servers[2].port`,
+    /// see [`SerdeError::field_path`]
+    pub fn field_path(&self) -> Option<&str> {
+        match self {
+            #[cfg(feature = "json")]
+            Self::Json(err) => err.field_path(),
+            #[cfg(feature = "yaml")]
+            Self::Yaml(err) => err.field_path(),
+            #[cfg(feature = "toml")]
+            Self::TomlDe(err) => err.field_path(),
+            #[cfg(feature = "toml")]
+            Self::TomlSer(err) => err.field_path(),
+            #[cfg(feature = "hcl")]
+            Self::Hcl(err) => err.field_path(),
+            _ => None,
+        }
+    }
+
+    /// The one-based line and column of a parse error, if known
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Io(_) | Self::Empty(_) => None,
+            #[cfg(feature = "json")]
+            Self::Json(err) if err.line() == 0 => None,
+            #[cfg(feature = "json")]
+            Self::Json(err) => Some((err.line(), err.column())),
+            #[cfg(feature = "yaml")]
+            Self::Yaml(err) => err.location().map(|l| (l.line(), l.column())),
+            #[cfg(feature = "toml")]
+            Self::TomlDe(err) => toml_line_col(err),
+            #[cfg(feature = "toml")]
+            Self::TomlSer(_) => None,
+            #[cfg(feature = "hcl")]
+            Self::Hcl(err) => err.location().map(|l| (l.line, l.col)),
+        }
+    }
+
+    /// The byte range of a parse error in the file content, if known
+    ///
+    /// Empty when only the position is known, only `yaml` and `toml` errors have one.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            #[cfg(feature = "yaml")]
+            Self::Yaml(err) => err.location().map(|l| l.index()..l.index()),
+            #[cfg(feature = "toml")]
+            Self::TomlDe(err) => err.span(),
+            _ => None,
+        }
+    }
+}
+
+// `toml` keeps the parsed content to render the position but only exposes the byte span,
+// the message starts with eg. `TOML parse error at line 2, column 7`
+#[cfg(feature = "toml")]
+fn toml_line_col(err: &toml::de::Error) -> Option<(usize, usize)> {
+    let message = err.to_string();
+    let (line, column) = message
+        .lines()
+        .next()?
+        .split_once(" at line ")?
+        .1
+        .split_once(", column ")?;
+    Some((line.parse().ok()?, column.parse().ok()?))
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::Empty(path) => write!(f, "{}: config file is empty", display_path(path)),
+            #[cfg(feature = "json")]
+            Self::Json(err) => write!(f, "json error: {}", err),
+            #[cfg(feature = "yaml")]
+            Self::Yaml(err) => write!(f, "yaml error: {}", err),
+            #[cfg(feature = "toml")]
+            // The `toml` message renders the content, which reports already show
+            Self::TomlDe(err) => {
+                write!(f, "toml parse error: ")?;
+                err.fmt_prefix(f)?;
+                f.write_str(&err.message().trim_end().replace('\n', ", "))?;
+                match toml_line_col(err) {
+                    Some((line, column)) => write!(f, " at line {} column {}", line, column),
+                    None => Ok(()),
+                }
+            }
+            #[cfg(feature = "toml")]
+            Self::TomlSer(err) => write!(f, "toml serialize error: {}", err),
+            #[cfg(feature = "hcl")]
+            Self::Hcl(err) => write!(f, "hcl error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Empty(_) => None,
+            #[cfg(feature = "json")]
+            Self::Json(err) => Some(err.get_ref()),
+            #[cfg(feature = "yaml")]
+            Self::Yaml(err) => Some(err.get_ref()),
+            #[cfg(feature = "toml")]
+            Self::TomlDe(err) => Some(err.get_ref()),
+            #[cfg(feature = "toml")]
+            Self::TomlSer(err) => Some(err.get_ref()),
+            #[cfg(feature = "hcl")]
+            Self::Hcl(err) => Some(err.get_ref()),
+        }
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(err: IoError) -> Self {
+        Self::Io(err)
+    }
+}
+
+// Conversions from a format error enum and from its serde error
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+macro_rules! from_format_error {
+    ($format:ident, $serde:ty, $variant:ident $(, $empty:ident)?) => {
+        impl From<$format> for Error {
+            fn from(err: $format) -> Self {
+                match err {
+                    $format::Io(err) => Self::Io(err),
+                    $format::Serde(err) => Self::$variant(err),
+                    $($format::$empty(path) => Self::Empty(path),)?
+                }
+            }
+        }
+
+        impl From<$serde> for Error {
+            fn from(err: $serde) -> Self {
+                Self::$variant(err.into())
+            }
+        }
+    };
+}
+
+#[cfg(feature = "json")]
+from_format_error!(JsonError, serde_json::Error, Json, Empty);
+#[cfg(feature = "yaml")]
+from_format_error!(YamlError, serde_yaml::Error, Yaml, Empty);
+#[cfg(feature = "toml")]
+from_format_error!(TomlParseError, toml::de::Error, TomlDe, Empty);
+#[cfg(feature = "toml")]
+from_format_error!(TomlSaveError, toml::ser::Error, TomlSer);
+#[cfg(feature = "hcl")]
+from_format_error!(HclError, hcl::Error, Hcl, Empty);
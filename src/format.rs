@@ -0,0 +1,128 @@
+use crate::{non_empty, Error, HomeConfig, IoResult, PathError};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// A config file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "hcl")]
+    Hcl,
+}
+
+impl Format {
+    // Whether the file name has an extension of the format, eg. `.yml` or `.yaml`
+    fn matches(self, path: &Path) -> bool {
+        let ext = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => ext.to_ascii_lowercase(),
+            None => return false,
+        };
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => ext == "json",
+            #[cfg(feature = "yaml")]
+            Format::Yaml => ext == "yaml" || ext == "yml",
+            #[cfg(feature = "toml")]
+            Format::Toml => ext == "toml",
+            #[cfg(feature = "hcl")]
+            Format::Hcl => ext == "hcl",
+        }
+    }
+}
+
+impl HomeConfig {
+    fn parse<T>(&self, format: Format) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(match format {
+            #[cfg(feature = "json")]
+            Format::Json => self.json()?,
+            #[cfg(feature = "yaml")]
+            Format::Yaml => self.yaml()?,
+            #[cfg(feature = "toml")]
+            Format::Toml => self.toml()?,
+            #[cfg(feature = "hcl")]
+            Format::Hcl => self.hcl()?,
+        })
+    }
+
+    /// Parse every profile in the config directory of `app_name`, keyed by file stem
+    ///
+    /// Profiles are the files with an extension of `format`, eg. `~/.config/app/work.toml`
+    /// is `work`. Files that fail to parse are skipped, a missing directory has no profiles.
+    pub fn load_all_profiles<T>(app_name: &str, format: Format) -> IoResult<HashMap<String, T>>
+    where
+        T: DeserializeOwned,
+    {
+        let dir = Self::try_with_config_dir(app_name, "")?;
+        let entries = match std::fs::read_dir(&dir.path) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(PathError::wrap(&dir.path, err)),
+        };
+        let mut profiles = HashMap::new();
+        for entry in entries {
+            let path = entry?.path();
+            if !format.matches(&path) || !path.is_file() {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|name| name.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            if let Ok(value) = dir.sibling(path).parse(format) {
+                profiles.insert(name, value);
+            }
+        }
+        Ok(profiles)
+    }
+
+    /// Parse the config file in `format`, replacing invalid UTF-8 with `U+FFFD` first
+    ///
+    /// A stray byte, eg. in a comment, then does not fail the whole file. Also returns
+    /// whether anything was replaced, see [`read_to_string_lossy`](Self::read_to_string_lossy).
+    pub fn parse_lossy<T>(&self, format: Format) -> Result<(T, bool), Error>
+    where
+        T: DeserializeOwned,
+    {
+        let (content, replaced) = self.read_to_string_lossy()?;
+        let bytes = non_empty(content.as_bytes()).ok_or_else(|| Error::Empty(self.path.clone()))?;
+        let value = match format {
+            #[cfg(feature = "json")]
+            Format::Json => crate::json_from_slice(bytes).map_err(|err| err.in_file(&self.path))?,
+            #[cfg(feature = "yaml")]
+            Format::Yaml => crate::yaml_from_slice(bytes).map_err(|err| err.in_file(&self.path))?,
+            #[cfg(feature = "toml")]
+            Format::Toml => crate::toml_from_slice(bytes).map_err(|err| err.in_file(&self.path))?,
+            #[cfg(feature = "hcl")]
+            Format::Hcl => crate::hcl_from_slice(bytes).map_err(|err| err.in_file(&self.path))?,
+        };
+        Ok((value, replaced))
+    }
+}
+
+#[cfg(feature = "json")]
+impl HomeConfig {
+    /// Parse the config file in `format` into a `json` value
+    ///
+    /// Gives one tree to walk whatever the format on disk, eg. `toml` tables become objects.
+    pub fn as_value(&self, format: Format) -> Result<serde_json::Value, Error> {
+        self.parse(format)
+    }
+
+    /// Whether two config files in `format` hold the same data
+    ///
+    /// Formatting, comments and key order are ignored, see [`as_value`](Self::as_value).
+    pub fn semantic_eq(&self, other: &HomeConfig, format: Format) -> Result<bool, Error> {
+        Ok(self.as_value(format)? == other.as_value(format)?)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{json_from_slice, HomeConfig, JsonError, LimitedReader};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Result as IoResult, Write};
+use std::marker::PhantomData;
+
+/// Incrementally write a `json` array, created by [`HomeConfig::json_array_writer`]
+///
+/// Each item is written on its own line, call [`finish`](Self::finish) to close the array.
+/// A writer dropped without finishing leaves an incomplete array, and write errors are lost.
+#[derive(Debug)]
+pub struct JsonArrayWriter {
+    writer: BufWriter<File>,
+    empty: bool,
+}
+
+impl JsonArrayWriter {
+    /// Append an item to the array
+    pub fn push<T>(&mut self, item: &T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        let sep: &[u8] = if self.empty { b"\n" } else { b",\n" };
+        self.writer.write_all(sep)?;
+        serde_json::to_writer(&mut self.writer, item)?;
+        self.empty = false;
+        Ok(())
+    }
+
+    /// Close the array and flush it to the file
+    pub fn finish(self) -> Result<(), JsonError> {
+        self.close()?;
+        Ok(())
+    }
+
+    /// Close the array, flush it to the file and the file to the storage device
+    pub fn finish_synced(self) -> Result<(), JsonError> {
+        self.close()?.sync_all()?;
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<File, JsonError> {
+        let end: &[u8] = if self.empty { b"]\n" } else { b"\n]\n" };
+        self.writer.write_all(end)?;
+        // Fails unless every buffered byte was written
+        self.writer
+            .into_inner()
+            .map_err(|err| JsonError::Io(err.into_error()))
+    }
+}
+
+/// Stream the items of a `json` array, created by [`HomeConfig::json_array_reader`]
+///
+/// Only one item is held in memory at a time.
+#[derive(Debug)]
+pub struct JsonArrayReader<T> {
+    reader: BufReader<LimitedReader<File>>,
+    first: bool,
+    done: bool,
+    buf: Vec<u8>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+fn syntax_error(msg: &str) -> JsonError {
+    JsonError::from(<serde_json::Error as serde::de::Error>::custom(msg))
+}
+
+impl<T> JsonArrayReader<T> {
+    fn peek(&mut self) -> IoResult<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn skip_whitespace(&mut self) -> IoResult<Option<u8>> {
+        loop {
+            match self.peek()? {
+                Some(b) if b.is_ascii_whitespace() => self.reader.consume(1),
+                other => return Ok(other),
+            }
+        }
+    }
+
+    // Copy the bytes of the next array item into `buf`, `false` at the end of the array
+    fn next_item(&mut self) -> Result<bool, JsonError> {
+        match self.skip_whitespace()? {
+            Some(b']') => {
+                self.reader.consume(1);
+                return Ok(false);
+            }
+            Some(b',') if !self.first => {
+                self.reader.consume(1);
+                self.skip_whitespace()?;
+            }
+            Some(_) if self.first => {}
+            Some(_) => return Err(syntax_error("expected `,` or `]` in json array")),
+            None => return Err(syntax_error("EOF while parsing json array")),
+        }
+        self.first = false;
+        self.buf.clear();
+
+        let (mut depth, mut in_string, mut escape) = (0usize, false, false);
+        while let Some(b) = self.peek()? {
+            if in_string {
+                self.buf.push(b);
+                self.reader.consume(1);
+                if escape {
+                    escape = false;
+                } else if b == b'\\' {
+                    escape = true;
+                } else if b == b'"' {
+                    in_string = false;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                continue;
+            }
+            match b {
+                b',' | b']' | b'}' if depth == 0 => break,
+                b if b.is_ascii_whitespace() && depth == 0 => break,
+                b'"' => in_string = true,
+                b'[' | b'{' => depth += 1,
+                b']' | b'}' => depth -= 1,
+                _ => {}
+            }
+            self.buf.push(b);
+            self.reader.consume(1);
+            if depth == 0 && matches!(b, b']' | b'}') {
+                break;
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<T> Iterator for JsonArrayReader<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = Result<T, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self
+            .next_item()
+            .and_then(|more| match more {
+                true => json_from_slice(&self.buf).map(Some),
+                false => Ok(None),
+            })
+            .transpose();
+        if !matches!(item, Some(Ok(_))) {
+            self.done = true;
+        }
+        item
+    }
+}
+
+impl HomeConfig {
+    /// Create the config file as an empty `json` array and write items incrementally
+    pub fn json_array_writer(&self) -> IoResult<JsonArrayWriter> {
+        let mut writer = BufWriter::new(self.open_write()?);
+        writer.write_all(b"[")?;
+        Ok(JsonArrayWriter {
+            writer,
+            empty: true,
+        })
+    }
+
+    /// Stream the items of a config file containing a `json` array
+    pub fn json_array_reader<T>(&self) -> Result<JsonArrayReader<T>, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let f = self.limit_reader(self.open_file()?);
+        let mut reader = JsonArrayReader {
+            reader: BufReader::new(f),
+            first: true,
+            done: false,
+            buf: Vec::new(),
+            _marker: PhantomData,
+        };
+        match reader.skip_whitespace()? {
+            Some(b'[') => reader.reader.consume(1),
+            _ => return Err(syntax_error("expected a json array")),
+        }
+        Ok(reader)
+    }
+}
@@ -0,0 +1,78 @@
+#[cfg(feature = "json")]
+use crate::JsonError;
+use crate::{HomeConfig, IoResult};
+#[cfg(feature = "json")]
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{Error as IoError, ErrorKind};
+
+fn keyring_error(err: keyring::Error) -> IoError {
+    match err {
+        keyring::Error::NoEntry => IoError::new(ErrorKind::NotFound, err),
+        err => IoError::other(err),
+    }
+}
+
+/// Store configs in the OS secret store instead of a file, with the `keyring` feature
+///
+/// The entry is keyed by the config directory name as service and the file name as user,
+/// eg. `app` and `config.json` for `~/.config/app/config.json`.
+/// Supported stores are the macOS Keychain, the Windows Credential Manager and the
+/// Secret Service on Linux and the BSDs, eg. GNOME Keyring or KWallet. The latter needs
+/// `libdbus` at build time, or the `keyring-vendored` feature to build it from source.
+impl HomeConfig {
+    fn keyring_entry(&self) -> IoResult<keyring::Entry> {
+        let name = |p: Option<&std::path::Path>| {
+            p.and_then(|p| p.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        keyring::Entry::new(&name(self.path.parent()), &name(Some(&self.path)))
+            .map_err(keyring_error)
+    }
+
+    /// Save content to the OS secret store
+    pub fn save_keyring<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+        self.keyring_entry()?
+            .set_secret(data.as_ref())
+            .map_err(keyring_error)
+    }
+
+    /// Read the content from the OS secret store
+    pub fn read_keyring_to_vec(&self) -> IoResult<Vec<u8>> {
+        self.keyring_entry()?.get_secret().map_err(keyring_error)
+    }
+
+    /// Read the content from the OS secret store into a string
+    pub fn read_keyring_to_string(&self) -> IoResult<String> {
+        String::from_utf8(self.read_keyring_to_vec()?)
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err))
+    }
+
+    /// Delete the content from the OS secret store, if it exists
+    pub fn delete_keyring(&self) -> IoResult<()> {
+        match self.keyring_entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(keyring_error(err)),
+        }
+    }
+
+    /// Parse the content from the OS secret store as `json`
+    #[cfg(feature = "json")]
+    pub fn json_keyring<T>(&self) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_keyring_to_vec()?;
+        crate::json_from_slice(&bytes)
+    }
+
+    /// Save struct to the OS secret store (`json` format)
+    #[cfg(feature = "json")]
+    pub fn save_json_keyring<T>(&self, data: T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        let s = Self::to_json_string(&data).map_err(self.serde_error())?;
+        self.save_keyring(s).map_err(JsonError::Io)
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{HomeConfig, JsonError};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+/// A persistent key/value store in a `json` object, created by [`HomeConfig::kv_store`]
+///
+/// Every mutation is saved to the file immediately.
+///
+/// ```no_run
+/// use home_config::HomeConfig;
+///
+/// let mut store = HomeConfig::with_config_dir("app", "settings.json").kv_store().unwrap();
+/// store.set("volume", 80).unwrap();
+/// let volume = store.get::<u32>("volume");
+/// // Some(80)
+/// ```
+#[derive(Debug, Clone)]
+pub struct KvStore {
+    config: HomeConfig,
+    map: Map<String, Value>,
+}
+
+impl KvStore {
+    /// Get the underlying config
+    pub fn config(&self) -> &HomeConfig {
+        &self.config
+    }
+
+    /// Get the value of `key`, `None` if it is missing or not a `T`
+    pub fn get<T>(&self, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        T::deserialize(self.map.get(key)?).ok()
+    }
+
+    /// Whether `key` is set
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Iterate over the keys, in sorted order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.map.keys().map(String::as_str)
+    }
+
+    /// Set `key` to `value` and save
+    pub fn set<T>(&mut self, key: &str, value: T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        let value = serde_json::to_value(value)?;
+        self.map.insert(key.to_string(), value);
+        self.config.save_json(&self.map)
+    }
+
+    /// Remove `key` and save, returns whether it was set
+    pub fn remove(&mut self, key: &str) -> Result<bool, JsonError> {
+        if self.map.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.config.save_json(&self.map)?;
+        Ok(true)
+    }
+}
+
+impl HomeConfig {
+    /// Open the config file as a key/value store, empty if the file does not exist or is empty
+    pub fn kv_store(&self) -> Result<KvStore, JsonError> {
+        let map = match self.json() {
+            Ok(map) => map,
+            Err(err) if err.is_not_found() || err.is_empty_file() => Map::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(KvStore {
+            config: self.clone(),
+            map,
+        })
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{HomeConfig, JsonError, SerdeError};
+use serde::de::DeserializeOwned;
+use serde_json::{Number, Value};
+use serde_path_to_error::Segment;
+use std::fmt;
+
+/// A recoverable problem found by [`HomeConfig::json_lenient`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    path: String,
+    message: String,
+}
+
+impl Warning {
+    /// Get the key path, eg. `servers[2].port`
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+// Same notation as `serde_path_to_error`, without the `Option` and newtype levels
+fn ignored_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{}]", ignored_path(parent), index),
+        Path::Map { parent, key } => match ignored_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+fn value_at<'a>(
+    mut value: &'a mut Value,
+    path: &serde_path_to_error::Path,
+) -> Option<&'a mut Value> {
+    for segment in path {
+        value = match segment {
+            Segment::Seq { index } => value.get_mut(index)?,
+            Segment::Map { key } => value.get_mut(key)?,
+            Segment::Enum { .. } | Segment::Unknown => return None,
+        };
+    }
+    Some(value)
+}
+
+fn rename_alias(value: &mut Value, old: &str, new: &str) -> Option<Warning> {
+    let (parent, key) = match old.rsplit_once('.') {
+        Some((parent, key)) => (parent.split('.').try_fold(value, |v, k| v.get_mut(k))?, key),
+        None => (value, old),
+    };
+    let object = parent.as_object_mut()?;
+    let old_value = object.remove(key)?;
+    let message = if object.contains_key(new) {
+        format!("deprecated, ignored because `{}` is set", new)
+    } else {
+        object.insert(new.to_string(), old_value);
+        format!("deprecated, renamed to `{}`", new)
+    };
+    Some(Warning {
+        path: old.to_string(),
+        message,
+    })
+}
+
+impl HomeConfig {
+    /// Parse the config file from `json` content, reporting recoverable problems as warnings
+    ///
+    /// Unknown keys are ignored and strings holding a number are converted where a number is expected.
+    /// Malformed content and other type mismatches are still errors.
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Settings {
+    ///     port: Option<u16>,
+    /// }
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "config.json");
+    /// let (settings, warnings) = config.json_lenient::<Settings>().unwrap();
+    /// for warning in warnings {
+    ///     eprintln!("warning: {}", warning);
+    /// }
+    /// ```
+    pub fn json_lenient<T>(&self) -> Result<(T, Vec<Warning>), JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        self.json_lenient_with_aliases(&[])
+    }
+
+    /// [`json_lenient`](Self::json_lenient), renaming deprecated keys first
+    ///
+    /// `aliases` maps the path of a deprecated key to its new name in the same object,
+    /// eg. `("server.hostname", "host")`.
+    pub fn json_lenient_with_aliases<T>(
+        &self,
+        aliases: &[(&str, &str)],
+    ) -> Result<(T, Vec<Warning>), JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut value: Value = self.json()?;
+        let mut warnings = aliases
+            .iter()
+            .filter_map(|(old, new)| rename_alias(&mut value, old, new))
+            .collect::<Vec<_>>();
+
+        let mut converted: Option<serde_path_to_error::Error<serde_json::Error>> = None;
+        loop {
+            let mut unknown = Vec::new();
+            let mut on_unknown = |path: serde_ignored::Path| unknown.push(ignored_path(&path));
+            let de = serde_ignored::Deserializer::new(&value, &mut on_unknown);
+            let err = match serde_path_to_error::deserialize(de) {
+                Ok(data) => {
+                    warnings.extend(unknown.into_iter().map(|path| Warning {
+                        path,
+                        message: "unknown key, ignored".to_string(),
+                    }));
+                    return Ok((data, warnings));
+                }
+                Err(err) => err,
+            };
+
+            // The number did not fit either, report the string that was there. `value` is
+            // dropped, so the string need not be put back
+            if let Some(original) = converted.take() {
+                if original.path().to_string() == err.path().to_string() {
+                    return Err(JsonError::Serde(SerdeError::with_field(original)));
+                }
+            }
+
+            // Only a string holding a number is converted. Each conversion removes a
+            // string, so this ends
+            let number = value_at(&mut value, err.path()).and_then(|leaf| {
+                let number = serde_json::from_str::<Number>(leaf.as_str()?.trim()).ok()?;
+                Some((leaf, number))
+            });
+            let (leaf, number) = match number {
+                Some(number) => number,
+                None => return Err(JsonError::Serde(SerdeError::with_field(err))),
+            };
+            warnings.push(Warning {
+                path: err.path().to_string(),
+                message: format!("converted string {} to a number", leaf),
+            });
+            *leaf = Value::Number(number);
+            converted = Some(err);
+        }
+    }
+}
@@ -54,227 +54,5229 @@
 
 #[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
 use serde::{de::DeserializeOwned, Serialize};
-use std::fs::{self, File};
-#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
-use std::io::Error as IoError;
-use std::io::{ErrorKind, Read, Result as IoResult};
+#[cfg(feature = "json")]
+use serde_json::value::RawValue;
+use std::cell::RefCell;
+#[cfg(feature = "json")]
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{mpsc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// First, for the macros used by the other modules
+#[macro_use]
+mod error;
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod async_io;
+#[cfg(feature = "json")]
+mod autosave;
+#[cfg(feature = "json")]
+mod config_file;
+#[cfg(all(
+    feature = "diagnostics",
+    any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl")
+))]
+mod diagnostic;
+mod disk_space;
+mod env;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+mod format;
+#[cfg(feature = "json")]
+mod json_array;
+#[cfg(feature = "keyring")]
+mod keychain;
+#[cfg(feature = "json")]
+mod kv_store;
+#[cfg(feature = "lenient")]
+mod lenient;
+mod lock;
+mod read_only;
+#[cfg(feature = "watch")]
+mod reloading;
+mod retry;
+#[cfg(feature = "schema")]
+mod schema;
+mod secure;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "json")]
+pub use autosave::AutosaveHandle;
+#[cfg(feature = "json")]
+pub use config_file::{ConfigFile, ConfigFileError, MergeStrategy};
+#[cfg(all(
+    feature = "diagnostics",
+    any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl")
+))]
+pub use diagnostic::ConfigDiagnostic;
+pub use env::expand_env_cow;
+pub use error::{ConfigErrorKind, Error, PathError, RuntimeDirError, SerdeError, TooLargeError};
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+pub use format::Format;
+#[cfg(feature = "json")]
+pub use json_array::{JsonArrayReader, JsonArrayWriter};
+#[cfg(feature = "json")]
+pub use kv_store::KvStore;
+#[cfg(feature = "lenient")]
+pub use lenient::Warning;
+pub use lock::ConfigLock;
+pub use read_only::ReadOnlyConfig;
+#[cfg(feature = "watch")]
+pub use reloading::{ReloadError, ReloadingConfig};
+pub use retry::{RetryError, RetryPolicy};
+#[cfg(feature = "schema")]
+pub use schema::ValidationError;
+pub use secure::{SecurityFinding, SecurityProblem, SecurityReport};
+#[cfg(feature = "watch-stream")]
+pub use watch::WatchStream;
+#[cfg(feature = "watch")]
+pub use watch::{is_temp_file, ChangeEvent, DirChangeEvent, WatchError, WatchHandle};
+
+// Resolved once per process, see `reset_home_cache`
+static HOME_CACHE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+thread_local! {
+    static HOME_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Override the home directory for configs created on the current thread
+///
+/// This is a testing aid, it makes paths deterministic without changing `$HOME`.
+/// Pass `None` to use the real home directory again.
+///
+/// ```
+/// use home_config::{set_home_override, HomeConfig};
+///
+/// set_home_override(Some("/tmp/home".into()));
+/// let config = HomeConfig::with_file("test.json");
+/// assert_eq!(config.path(), std::path::Path::new("/tmp/home/test.json"));
+/// set_home_override(None);
+/// ```
+pub fn set_home_override(home: Option<PathBuf>) {
+    HOME_OVERRIDE.with(|h| *h.borrow_mut() = home);
+}
+
+/// Forget the cached home directory, it is resolved again by the next config
+///
+/// eg. after changing `HOME` in tests
+pub fn reset_home_cache() {
+    *HOME_CACHE.write().unwrap() = None;
+}
+
+fn cached_home_dir() -> PathBuf {
+    if let Some(home) = HOME_CACHE.read().unwrap().as_ref() {
+        return home.clone();
+    }
+    HOME_CACHE
+        .write()
+        .unwrap()
+        .get_or_insert_with(|| dirs::home_dir().expect("Get home dir"))
+        .clone()
+}
+
+#[cfg(feature = "json")]
+fn merge_json(base: &mut serde_json::Value, layer: serde_json::Value) {
+    match (base, layer) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(base) => merge_json(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+// Objects are compared key by key, a missing key is `null`
+#[cfg(feature = "json")]
+fn json_diff(old: serde_json::Value, new: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    match (old, new) {
+        (old, new) if old == new => Value::Null,
+        (Value::Object(mut old), Value::Object(new)) => {
+            let mut diff = serde_json::Map::new();
+            for (key, new) in new {
+                let old = old.remove(&key).unwrap_or_default();
+                match json_diff(old, new) {
+                    Value::Null => {}
+                    changed => {
+                        diff.insert(key, changed);
+                    }
+                }
+            }
+            for (key, old) in old {
+                diff.insert(key, json_diff(old, Value::Null));
+            }
+            Value::Object(diff)
+        }
+        (old, new) => serde_json::json!({ "old": old, "new": new }),
+    }
+}
+
+// Insert the leaves of `value` under `prefix`, arrays and empty objects are leaves
+#[cfg(feature = "json")]
+fn flatten_json(
+    prefix: &str,
+    value: serde_json::Value,
+    map: &mut std::collections::BTreeMap<String, serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                match prefix {
+                    "" => flatten_json(&key, value, map),
+                    prefix => flatten_json(&format!("{}.{}", prefix, key), value, map),
+                }
+            }
+        }
+        value => {
+            map.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+// Format as `YYYY-MM-DDTHH-MM-SS.mmmZ` in UTC
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // Days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(feature = "json")]
+fn fill_json_defaults(value: &mut serde_json::Value, defaults: serde_json::Value) {
+    if let (serde_json::Value::Object(value), serde_json::Value::Object(defaults)) =
+        (value, defaults)
+    {
+        for (key, default) in defaults {
+            match value.get_mut(&key) {
+                Some(value) => fill_json_defaults(value, default),
+                None => {
+                    value.insert(key, default);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+fn merge_toml(base: &mut toml::Value, layer: toml::Value) {
+    match (base, layer) {
+        (toml::Value::Table(base), toml::Value::Table(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(base) => merge_toml(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+// Replace the table at the dotted `path` with an inline table, if there is one
+#[cfg(feature = "toml")]
+fn inline_toml_table(mut table: &mut toml_edit::Table, path: &str) {
+    let (parents, key) = match path.rsplit_once('.') {
+        Some((parents, key)) => (parents.split('.').collect(), key),
+        None => (Vec::new(), path),
+    };
+    for parent in parents {
+        table = match table
+            .get_mut(parent)
+            .and_then(toml_edit::Item::as_table_mut)
+        {
+            Some(inner) => inner,
+            None => return,
+        };
+    }
+    if let Some(toml_edit::Item::Table(inner)) = table.get_mut(key) {
+        let inline = std::mem::take(inner).into_inline_table();
+        table.insert(key, toml_edit::value(inline));
+        // The key was formatted for a `[header]`, without the spaces around `=`
+        if let Some(mut key) = table.key_mut(key) {
+            key.leaf_decor_mut().clear();
+        }
+    }
+}
+
+// `None` for a missing file, other errors are kept
+fn optional<T, E>(result: Result<T, E>, not_found: impl Fn(&E) -> bool) -> Result<Option<T>, E> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if not_found(&err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// Skip the UTF-8 byte order mark written by some Windows tools
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+pub(crate) fn strip_bom(bytes: &[u8]) -> &[u8] {
+    bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes)
+}
+
+// Deserialize through `serde_path_to_error` with the `field-path` feature, so errors
+// record the path of the field they happened in. Every parse goes through here.
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+pub(crate) fn deserialize_seed<'de, S, D>(seed: S, de: D) -> Result<S::Value, SerdeError<D::Error>>
+where
+    S: serde::de::DeserializeSeed<'de>,
+    D: serde::Deserializer<'de>,
+{
+    #[cfg(feature = "field-path")]
+    {
+        let mut track = serde_path_to_error::Track::new();
+        let de = serde_path_to_error::Deserializer::new(de, &mut track);
+        seed.deserialize(de).map_err(|err| {
+            SerdeError::with_field(serde_path_to_error::Error::new(track.path(), err))
+        })
+    }
+    #[cfg(not(feature = "field-path"))]
+    seed.deserialize(de).map_err(SerdeError::from)
+}
+
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+pub(crate) fn deserialize<'de, T, D>(de: D) -> Result<T, SerdeError<D::Error>>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    deserialize_seed(std::marker::PhantomData, de)
+}
+
+#[cfg(feature = "json")]
+pub(crate) fn json_from_slice<'de, T>(bytes: &'de [u8]) -> Result<T, JsonError>
+where
+    T: serde::Deserialize<'de>,
+{
+    let mut de = serde_json::Deserializer::from_slice(strip_bom(bytes));
+    let value = deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+#[cfg(feature = "yaml")]
+pub(crate) fn yaml_from_slice<T>(bytes: &[u8]) -> Result<T, YamlError>
+where
+    T: DeserializeOwned,
+{
+    let de = serde_yaml::Deserializer::from_slice(strip_bom(bytes));
+    Ok(deserialize(de)?)
+}
+
+// `toml` only parses strings, invalid UTF-8 is reported as a parse error like any other
+#[cfg(feature = "toml")]
+pub(crate) fn toml_from_slice<T>(bytes: &[u8]) -> Result<T, TomlParseError>
+where
+    T: DeserializeOwned,
+{
+    let s = std::str::from_utf8(strip_bom(bytes))
+        .map_err(|err| TomlParseError::from(<toml::de::Error as serde::de::Error>::custom(err)))?;
+    Ok(deserialize(toml::Deserializer::new(s))?)
+}
+
+#[cfg(feature = "hcl")]
+pub(crate) fn hcl_from_slice<T>(bytes: &[u8]) -> Result<T, HclError>
+where
+    T: DeserializeOwned,
+{
+    let s = std::str::from_utf8(strip_bom(bytes)).map_err(hcl::Error::from)?;
+    let de = hcl::de::Deserializer::from_str(s)?;
+    Ok(deserialize(de)?)
+}
+
+// The content to parse without the byte order mark, `None` if only whitespace is left
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+pub(crate) fn non_empty(bytes: &[u8]) -> Option<&[u8]> {
+    let bytes = strip_bom(bytes);
+    match bytes.iter().all(u8::is_ascii_whitespace) {
+        true => None,
+        false => Some(bytes),
+    }
+}
+
+// Replace comments outside of strings with spaces, keeping line and column numbers
+#[cfg(feature = "json")]
+fn strip_json_comments(bytes: &[u8]) -> Vec<u8> {
+    let mut out = bytes.to_vec();
+    let (mut i, mut in_string) = (0, false);
+    while i < out.len() {
+        match (in_string, out[i], out.get(i + 1)) {
+            (true, b'\\', _) => i += 1,
+            (_, b'"', _) => in_string = !in_string,
+            (false, b'/', Some(b'/')) => {
+                while i < out.len() && out[i] != b'\n' {
+                    out[i] = b' ';
+                    i += 1;
+                }
+                continue;
+            }
+            (false, b'/', Some(b'*')) => {
+                let end = out[i + 2..]
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(out.len(), |p| i + 2 + p + 2);
+                for b in &mut out[i..end] {
+                    if *b != b'\n' {
+                        *b = b' ';
+                    }
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    out
+}
+
+// The path with the home directory replaced by `~`
+fn display_path(path: &Path) -> String {
+    match path.strip_prefix(home_dir()) {
+        Ok(relative) => Path::new("~").join(relative).display().to_string(),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+// An app name must be a single path segment, `<>:"|?*` are rejected by Windows
+fn check_app_name(app_name: &str) -> IoResult<()> {
+    let invalid = |c: char| {
+        c.is_control() || matches!(c, '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*')
+    };
+    if matches!(app_name, "" | "." | "..") || app_name.contains(invalid) {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!("invalid app name {:?}", app_name),
+        ));
+    }
+    Ok(())
+}
 
 fn home_dir() -> PathBuf {
-    dirs::home_dir().expect("Get home dir")
+    HOME_OVERRIDE
+        .with(|h| h.borrow().clone())
+        .unwrap_or_else(cached_home_dir)
 }
 
 /// Serde `json` error
 #[derive(Debug)]
 #[cfg(feature = "json")]
+#[non_exhaustive]
 pub enum JsonError {
     Io(IoError),
-    Serde(serde_json::Error),
+    Serde(SerdeError<serde_json::Error>),
+    /// The config file is empty or only whitespace
+    Empty(PathBuf),
+}
+
+// The entries of a `json` object in their order, with the values as written
+#[cfg(feature = "json")]
+struct JsonEntries(Vec<(String, Box<RawValue>)>);
+
+#[cfg(feature = "json")]
+impl<'de> serde::Deserialize<'de> for JsonEntries {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = JsonEntries;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a json object at the top level")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(JsonEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+#[cfg(feature = "json")]
+impl Serialize for JsonEntries {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+// A stream failing with a `TooLargeError` once more bytes than the size limit were read
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub(crate) struct LimitedReader<R> {
+    inner: std::io::Take<R>,
+    config: HomeConfig,
+    read: u64,
+}
+
+#[cfg(feature = "json")]
+impl<R> LimitedReader<R> {
+    // Whether the limit was exceeded so far
+    pub(crate) fn check(&self) -> IoResult<()> {
+        self.config.check_size(self.read)
+    }
+}
+
+#[cfg(feature = "json")]
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        self.check()?;
+        Ok(n)
+    }
+}
+
+/// The content of a `json` config, for parsing into types that borrow from it
+///
+/// Created by [`HomeConfig::json_borrowed`]. Values returned by [`parse`](Self::parse)
+/// borrow `&str` and `Cow<str>` fields from the reader, so they cannot outlive it.
+///
+/// ```no_run
+/// use home_config::HomeConfig;
+/// use serde::Deserialize;
+/// use std::borrow::Cow;
+///
+/// #[derive(Deserialize)]
+/// struct People<'a> {
+///     #[serde(borrow)]
+///     name: Cow<'a, str>,
+/// }
+///
+/// let reader = HomeConfig::with_file("test.json").json_borrowed().unwrap();
+/// let people = reader.parse::<People>().unwrap();
+/// // `reader` must be kept alive while `people` is used
+/// ```
+#[derive(Debug, Clone)]
+#[cfg(feature = "json")]
+pub struct JsonReader {
+    content: String,
+    path: PathBuf,
+}
+
+#[cfg(feature = "json")]
+impl JsonReader {
+    /// Parse the content, borrowing from it where possible
+    pub fn parse<'a, T>(&'a self) -> Result<T, JsonError>
+    where
+        T: serde::Deserialize<'a>,
+    {
+        json_from_slice(self.content.as_bytes()).map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Get the raw content
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
 }
 
 /// Serde `yaml` error
 #[derive(Debug)]
 #[cfg(feature = "yaml")]
+#[non_exhaustive]
 pub enum YamlError {
     Io(IoError),
-    Serde(serde_yaml::Error),
+    Serde(SerdeError<serde_yaml::Error>),
+    /// The config file is empty or only whitespace
+    Empty(PathBuf),
 }
 
 /// Serde `toml` parse error
 #[derive(Debug)]
 #[cfg(feature = "toml")]
+#[non_exhaustive]
 pub enum TomlParseError {
     Io(IoError),
-    Serde(toml::de::Error),
+    Serde(SerdeError<toml::de::Error>),
+    /// The config file is empty or only whitespace
+    Empty(PathBuf),
 }
 
 /// Serde `toml` save error
 #[derive(Debug)]
 #[cfg(feature = "toml")]
+#[non_exhaustive]
 pub enum TomlSaveError {
     Io(IoError),
-    Serde(toml::ser::Error),
+    Serde(SerdeError<toml::ser::Error>),
 }
 
 /// Serde `hcl` error
 #[derive(Debug)]
 #[cfg(feature = "hcl")]
+#[non_exhaustive]
 pub enum HclError {
     Io(IoError),
-    Serde(hcl::Error),
+    Serde(SerdeError<hcl::Error>),
+    /// The config file is empty or only whitespace
+    Empty(PathBuf),
+}
+
+// Display, Error and From conversions for the format error enums
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+macro_rules! format_error {
+    ($name:ident, $serde:ty, $what:literal, $kind:expr $(, $empty:ident)?) => {
+        impl $name {
+            /// Get the category of the error
+            pub fn kind(&self) -> ConfigErrorKind {
+                match self {
+                    Self::Io(err) => error::io_kind(err),
+                    Self::Serde(err) => $kind(err),
+                    $(Self::$empty(_) => ConfigErrorKind::Empty,)?
+                }
+            }
+
+            kind_predicates!();
+
+            /// Get the config file path, see [`SerdeError::path`] and [`PathError`]
+            pub fn path(&self) -> Option<&Path> {
+                match self {
+                    Self::Io(err) => PathError::of(err).map(PathError::path),
+                    Self::Serde(err) => err.path(),
+                    $(Self::$empty(path) => Some(path),)?
+                }
+            }
+
+            // Only the parse errors are mapped, saves use `HomeConfig::serde_error`
+            $(
+                pub(crate) fn in_file(self, path: &Path) -> Self {
+                    match self {
+                        Self::Io(err) => Self::Io(PathError::wrap(path, err)),
+                        Self::Serde(err) => Self::Serde(err.in_file(path)),
+                        Self::$empty(path) => Self::$empty(path),
+                    }
+                }
+            )?
+
+            /// The path of the field a parse error happened in, eg. `servers[2].port`,
+            /// see [`SerdeError::field_path`]
+            pub fn field_path(&self) -> Option<&str> {
+                match self {
+                    Self::Serde(err) => err.field_path(),
+                    _ => None,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    Self::Io(err) => write!(f, "io error: {}", err),
+                    Self::Serde(err) => write!(f, concat!($what, " error: {}"), err),
+                    $(Self::$empty(path) => {
+                        write!(f, "{}: config file is empty", display_path(path))
+                    })?
+                }
+            }
+        }
+
+        impl std::error::Error for $name {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                match self {
+                    Self::Io(err) => Some(err),
+                    Self::Serde(err) => Some(err.get_ref()),
+                    $(Self::$empty(_) => None,)?
+                }
+            }
+        }
+
+        impl From<IoError> for $name {
+            fn from(err: IoError) -> Self {
+                Self::Io(err)
+            }
+        }
+
+        impl From<$serde> for $name {
+            fn from(err: $serde) -> Self {
+                Self::Serde(err.into())
+            }
+        }
+
+        impl From<SerdeError<$serde>> for $name {
+            fn from(err: SerdeError<$serde>) -> Self {
+                Self::Serde(err)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "json")]
+format_error!(
+    JsonError,
+    serde_json::Error,
+    "json",
+    error::json_kind,
+    Empty
+);
+#[cfg(feature = "yaml")]
+format_error!(
+    YamlError,
+    serde_yaml::Error,
+    "yaml",
+    |_| ConfigErrorKind::Syntax,
+    Empty
+);
+#[cfg(feature = "toml")]
+format_error!(
+    TomlParseError,
+    toml::de::Error,
+    "toml parse",
+    |_| ConfigErrorKind::Syntax,
+    Empty
+);
+#[cfg(feature = "toml")]
+format_error!(TomlSaveError, toml::ser::Error, "toml serialize", |_| {
+    ConfigErrorKind::Serialize
+});
+#[cfg(feature = "hcl")]
+format_error!(
+    HclError,
+    hcl::Error,
+    "hcl",
+    |_| ConfigErrorKind::Syntax,
+    Empty
+);
+
+/// The last content read by [`HomeConfig::read_to_string_cached`]
+#[derive(Debug, Clone, Default)]
+pub struct ConfigCache {
+    version: Option<FileVersion>,
+    content: String,
+}
+
+// Compared to detect changes, as saves within the resolution of the modification time
+// keep it, and a file replaced by a rename has a new inode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileVersion {
+    modified: SystemTime,
+    len: u64,
+    #[cfg(unix)]
+    ino: u64,
+}
+
+impl FileVersion {
+    fn of(meta: &fs::Metadata) -> IoResult<Self> {
+        Ok(Self {
+            modified: meta.modified()?,
+            len: meta.len(),
+            #[cfg(unix)]
+            ino: std::os::unix::fs::MetadataExt::ino(meta),
+        })
+    }
+}
+
+impl ConfigCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the last content read
+    pub fn content(&self) -> &str {
+        &self.content
+    }
 }
 
 /// Use the configuration file in the current user directory
 #[derive(Debug, Clone)]
 pub struct HomeConfig {
     path: PathBuf,
+    retry: Option<RetryPolicy>,
+    max_size: Option<u64>,
 }
 
 impl HomeConfig {
     /// Parse or create configuration file
     ///
     /// eg. `/home/name/.config/app/config`
+    ///
+    /// An empty `app_name` would put the file directly in `~/.config`, shared with every other app,
+    /// so it panics in debug builds. Use [`try_with_config_dir`](Self::try_with_config_dir) to get an error instead.
     pub fn with_config_dir<P: AsRef<Path>>(app_name: &'static str, file_name: P) -> Self {
+        debug_assert!(!app_name.is_empty(), "app name is empty");
         Self {
             path: home_dir().join(".config").join(app_name).join(file_name),
+            retry: None,
+            max_size: None,
         }
     }
 
+    /// [`with_config_dir`](Self::with_config_dir) for an app name from external input
+    ///
+    /// Fails with `InvalidInput` if `app_name` is not a single path segment, ie. it is empty, `.` or `..`,
+    /// contains a path separator, a control character or one of `<>:"|?*` which Windows rejects.
+    pub fn try_with_config_dir<P: AsRef<Path>>(app_name: &str, file_name: P) -> IoResult<Self> {
+        check_app_name(app_name)?;
+        Ok(Self {
+            path: home_dir().join(".config").join(app_name).join(file_name),
+            retry: None,
+            max_size: None,
+        })
+    }
+
+    /// Use a file in the runtime directory of `app_name`, eg. a lock file or a socket
+    ///
+    /// eg. `/run/user/1000/app/app.sock`
+    ///
+    /// The directory is `XDG_RUNTIME_DIR`, which is only cleaned up on logout or reboot.
+    /// On Linux, it is an error if the variable is not set, elsewhere the temporary
+    /// directory is used instead. `app_name` is checked like [`try_with_config_dir`](Self::try_with_config_dir).
+    pub fn with_runtime_dir<P: AsRef<Path>>(
+        app_name: impl AsRef<str>,
+        file: P,
+    ) -> Result<Self, RuntimeDirError> {
+        let app_name = app_name.as_ref();
+        check_app_name(app_name)?;
+        let dir = match std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+            // Relative paths are invalid and should be ignored, says the spec
+            Some(dir) if dir.is_absolute() => dir,
+            _ if cfg!(target_os = "linux") => return Err(RuntimeDirError::NotSet),
+            _ => std::env::temp_dir(),
+        };
+        Ok(Self {
+            path: dir.join(app_name).join(file),
+            retry: None,
+            max_size: None,
+        })
+    }
+
     /// Parse or create configuration file
     ///
     /// eg. `/home/name/test.json`
     pub fn with_file<P: AsRef<Path>>(p: P) -> Self {
         Self {
             path: home_dir().join(p),
+            retry: None,
+            max_size: None,
         }
     }
 
-    /// Get the configuration file path
-    pub fn path(&self) -> &PathBuf {
-        &self.path
+    /// Use a configuration file in the current working directory, eg. a project-local `./.apprc`
+    ///
+    /// Fails if the current directory cannot be read, eg. if it was deleted.
+    pub fn with_cwd_file<P: AsRef<Path>>(p: P) -> IoResult<Self> {
+        Ok(Self {
+            path: std::env::current_dir()?.join(p),
+            retry: None,
+            max_size: None,
+        })
     }
 
-    /// Read the entire contents of a file into a string
-    pub fn read_to_string(&self) -> IoResult<String> {
-        fs::read_to_string(&self.path)
+    /// Use a configuration file in the cargo project directory, eg. a test fixture
+    ///
+    /// Read from the `CARGO_MANIFEST_DIR` environment variable when running,
+    /// which `cargo run` and `cargo test` set, so it is `None` for an installed binary.
+    pub fn from_manifest_dir<P: AsRef<Path>>(p: P) -> Option<Self> {
+        let dir = std::env::var_os("CARGO_MANIFEST_DIR")?;
+        Some(Self {
+            path: PathBuf::from(dir).join(p),
+            retry: None,
+            max_size: None,
+        })
     }
 
-    /// Read the entire contents of a file into a `Vec<u8>`
-    pub fn read_to_vec(&self) -> IoResult<Vec<u8>> {
-        let mut f = File::open(&self.path)?;
-        let mut buf = Vec::new();
-        f.read_to_end(&mut buf)?;
-        Ok(buf)
+    /// Retry transient IO errors when reading or saving the file
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
     }
 
-    /// Parse the config file from `json` content
-    #[cfg(feature = "json")]
-    pub fn json<T>(&self) -> Result<T, JsonError>
-    where
-        T: DeserializeOwned,
-    {
-        let f = File::open(&self.path).map_err(JsonError::Io)?;
-        serde_json::from_reader(f).map_err(JsonError::Serde)
+    /// Refuse to read files larger than `limit` bytes, eg. a path misconfigured to a disk image
+    ///
+    /// Reads and parses fail with a [`TooLargeError`] instead, which is also checked
+    /// while reading in case the file grows. Streaming parses stop reading after the limit,
+    /// and [`json_auto`](Self::json_auto) also limits the decompressed content.
+    /// [`open_read`](Self::open_read) checks the size when opening, the returned `File`
+    /// itself is not limited.
+    pub fn with_max_size(mut self, limit: u64) -> Self {
+        self.max_size = Some(limit);
+        self
     }
 
-    /// Parse the config file from `yaml` content
-    #[cfg(feature = "yaml")]
-    pub fn yaml<T>(&self) -> Result<T, YamlError>
-    where
-        T: DeserializeOwned,
-    {
-        let f = File::open(&self.path).map_err(YamlError::Io)?;
-        serde_yaml::from_reader(f).map_err(YamlError::Serde)
+    fn sibling(&self, path: PathBuf) -> Self {
+        Self {
+            path,
+            retry: self.retry,
+            max_size: self.max_size,
+        }
     }
 
-    /// Parse the config file from `toml` content
-    #[cfg(feature = "toml")]
-    pub fn toml<T>(&self) -> Result<T, TomlParseError>
-    where
-        T: DeserializeOwned,
-    {
-        let bytes = self.read_to_vec().map_err(TomlParseError::Io)?;
-        toml::from_slice(&bytes).map_err(TomlParseError::Serde)
+    /// Get a config with `suffix` appended to the file stem
+    ///
+    /// eg. `config.toml` with suffix `.lock` is `config.lock.toml`
+    pub fn with_name_suffix(&self, suffix: &str) -> HomeConfig {
+        let mut name = self.path.file_stem().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        if let Some(ext) = self.path.extension() {
+            name.push(".");
+            name.push(ext);
+        }
+        self.sibling(self.path.with_file_name(name))
     }
 
-    /// Parse the config file from `hcl` content
-    #[cfg(feature = "hcl")]
-    pub fn hcl<T>(&self) -> Result<T, HclError>
-    where
-        T: DeserializeOwned,
-    {
-        let f = File::open(&self.path).map_err(HclError::Io)?;
-        hcl::from_reader(f).map_err(HclError::Serde)
+    /// Get a config with the current UTC time appended to the file stem
+    ///
+    /// eg. `config.toml` is `config.2024-01-15T10-30-00.123Z.toml`, `:` is avoided for Windows
+    pub fn with_timestamp_suffix(&self) -> HomeConfig {
+        self.with_name_suffix(&format!(".{}", timestamp(SystemTime::now())))
     }
 
-    fn create_parent_dir(&self) -> IoResult<()> {
-        if !self.path.exists() {
-            if let Some(parent) = self.path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-        }
-        Ok(())
+    /// Get a config with `prefix` prepended to the file name
+    ///
+    /// eg. `config.toml` with prefix `old.` is `old.config.toml`
+    pub fn with_name_prefix(&self, prefix: &str) -> HomeConfig {
+        let mut name = std::ffi::OsString::from(prefix);
+        name.push(self.path.file_name().unwrap_or_default());
+        self.sibling(self.path.with_file_name(name))
     }
 
-    /// Save content to local file
-    pub fn save<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+    /// Get the configuration file path
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Unwrap the configuration file path
+    ///
+    /// ```
+    /// use home_config::HomeConfig;
+    ///
+    /// let config = HomeConfig::with_file(".apprc");
+    /// let path = config.clone().into_path();
+    /// assert_eq!(&path, config.path());
+    /// ```
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+
+    /// Get the path for display, with the home directory replaced by `~`
+    ///
+    /// eg. `~/.config/app/config`
+    pub fn display_path(&self) -> String {
+        display_path(&self.path)
+    }
+
+    /// Get a config for the directory containing the file, `None` for a root path
+    ///
+    /// eg. `~/.config/app` for `~/.config/app/config`
+    pub fn config_dir(&self) -> Option<HomeConfig> {
+        let dir = self.path.parent()?;
+        Some(self.sibling(dir.to_path_buf()))
+    }
+
+    /// Whether the path is an existing directory
+    pub fn is_config_dir(&self) -> bool {
+        self.path.is_dir()
+    }
+
+    /// Whether the file exists and can be opened for reading
+    ///
+    /// Unlike `path().exists()`, a file without read permission is `false`.
+    pub fn exists_and_readable(&self) -> bool {
+        File::open(&self.path).is_ok()
+    }
+
+    /// Whether the file exists and can be opened for writing, the file is not changed
+    pub fn exists_and_writable(&self) -> bool {
+        OpenOptions::new().write(true).open(&self.path).is_ok()
+    }
+
+    /// Get the absolute path with all symlinks and `.`/`..` components resolved
+    ///
+    /// The file must exist.
+    pub fn canonical_path(&self) -> IoResult<PathBuf> {
+        fs::canonicalize(&self.path).map_err(|err| PathError::wrap(&self.path, err))
+    }
+
+    /// Get a hash of the canonical path, eg. as a cache key
+    ///
+    /// Falls back to the path as given if the file does not exist.
+    /// The hash is stable within a build, but may change between Rust versions.
+    pub fn path_hash(&self) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        match self.canonical_path() {
+            Ok(path) => path.hash(&mut hasher),
+            Err(_) => self.path.hash(&mut hasher),
+        }
+        hasher.finish()
+    }
+
+    /// Whether both configs are the same file, following symlinks and `.`/`..` components
+    ///
+    /// Unlike `==`, which compares the paths as given. Both files must exist.
+    pub fn same_file(&self, other: &HomeConfig) -> IoResult<bool> {
+        Ok(self.canonical_path()? == other.canonical_path()?)
+    }
+
+    fn retry<T, F>(&self, mut f: F) -> IoResult<T>
+    where
+        F: FnMut() -> IoResult<T>,
+    {
+        let result = match &self.retry {
+            Some(policy) => policy.run(f),
+            None => f(),
+        };
+        result.map_err(|err| PathError::wrap(&self.path, err))
+    }
+
+    fn open_file(&self) -> IoResult<File> {
+        let f = self.retry(|| File::open(&self.path))?;
+        let size = f
+            .metadata()
+            .map_err(|err| PathError::wrap(&self.path, err))?;
+        self.check_size(size.len())?;
+        Ok(f)
+    }
+
+    fn check_size(&self, size: u64) -> IoResult<()> {
+        match self.max_size {
+            Some(limit) if size > limit => {
+                let err = TooLargeError { size, limit };
+                Err(PathError::wrap(
+                    &self.path,
+                    IoError::new(ErrorKind::InvalidData, err),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Read all of a file, stopping one byte after the size limit
+    fn read_all(&self, f: File, buf: &mut Vec<u8>) -> IoResult<()> {
+        let limit = self
+            .max_size
+            .map_or(u64::MAX, |limit| limit.saturating_add(1));
+        f.take(limit)
+            .read_to_end(buf)
+            .map_err(|err| PathError::wrap(&self.path, err))?;
+        self.check_size(buf.len() as u64)
+    }
+
+    // Stop reading a stream one byte after the size limit, see `LimitedReader`
+    #[cfg(feature = "json")]
+    fn limit_reader<R: Read>(&self, reader: R) -> LimitedReader<R> {
+        let limit = self
+            .max_size
+            .map_or(u64::MAX, |limit| limit.saturating_add(1));
+        LimitedReader {
+            inner: reader.take(limit),
+            config: self.clone(),
+            read: 0,
+        }
+    }
+
+    fn write_file(&self, data: &[u8]) -> IoResult<()> {
+        self.retry(|| fs::write(&self.path, data))
+    }
+
+    // Attach the config file path to a serde error
+    #[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+    fn serde_error<E>(&self) -> impl Fn(E) -> SerdeError<E> + '_ {
+        move |err| SerdeError::from(err).in_file(&self.path)
+    }
+
+    // Write a new temporary file next to the config and rename it over the config,
+    // so readers never see a partial file, even after a crash. An existing file keeps
+    // its permissions.
+    #[cfg(feature = "json")]
+    fn replace_file<E, F>(&self, write: F) -> Result<(), E>
+    where
+        E: From<IoError>,
+        F: FnOnce(&mut std::io::BufWriter<File>) -> Result<(), E>,
+    {
+        self.write_temp_file(true, write).map(|_| ())
+    }
+
+    // Write a new temporary file next to the config and move it into place, replacing
+    // the config or, without `replace`, only if there is none yet. Returns whether it
+    // was moved.
+    fn write_temp_file<E, F>(&self, replace: bool, write: F) -> Result<bool, E>
+    where
+        E: From<IoError>,
+        F: FnOnce(&mut std::io::BufWriter<File>) -> Result<(), E>,
+    {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
         self.create_parent_dir()?;
-        fs::write(&self.path, data.as_ref())
+        let mut name = OsString::from(".");
+        name.push(self.path.file_name().unwrap_or_default());
+        // Unique for every save, in this or any other process
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        name.push(format!(".{}.{}.tmp", std::process::id(), n));
+        let tmp = self.sibling(self.path.with_file_name(name));
+        let f = tmp.retry(|| {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp.path)
+        })?;
+
+        let result = (|| {
+            if let Ok(meta) = fs::metadata(&self.path) {
+                f.set_permissions(meta.permissions())
+                    .map_err(|err| PathError::wrap(&tmp.path, err))?;
+            }
+            let mut writer = std::io::BufWriter::new(f);
+            write(&mut writer)?;
+            let f = writer
+                .into_inner()
+                .map_err(|err| PathError::wrap(&tmp.path, err.into_error()))?;
+            f.sync_all()
+                .map_err(|err| PathError::wrap(&tmp.path, err))?;
+            if replace {
+                self.retry(|| fs::rename(&tmp.path, &self.path))?;
+                return Ok(true);
+            }
+            Ok(self.link_new(&tmp.path, |from, to| fs::hard_link(from, to))?)
+        })();
+        if result.is_err() || !replace {
+            let _ = fs::remove_file(&tmp.path);
+        }
+        result
     }
 
-    /// Save struct to local file (`json` format)
+    // Put `tmp` in place of the config unless it exists, returns whether it did
+    fn link_new<L>(&self, tmp: &Path, link: L) -> IoResult<bool>
+    where
+        L: Fn(&Path, &Path) -> IoResult<()>,
+    {
+        // Unlike a rename, linking fails if the config exists
+        let err = match self.retry(|| link(tmp, &self.path)) {
+            Ok(()) => return Ok(true),
+            Err(err) => err,
+        };
+        match err.kind() {
+            ErrorKind::AlreadyExists => Ok(false),
+            // No hard links on eg. FAT or some network shares, copy into a new file instead
+            ErrorKind::Unsupported | ErrorKind::PermissionDenied => {
+                let mut f = match OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&self.path)
+                {
+                    Ok(f) => f,
+                    Err(err) if err.kind() == ErrorKind::AlreadyExists => return Ok(false),
+                    Err(err) => return Err(PathError::wrap(&self.path, err)),
+                };
+                let result = File::open(tmp)
+                    .and_then(|mut src| std::io::copy(&mut src, &mut f))
+                    .and_then(|_| f.sync_all());
+                if let Err(err) = result {
+                    let _ = fs::remove_file(&self.path);
+                    return Err(PathError::wrap(&self.path, err));
+                }
+                Ok(true)
+            }
+            _ => Err(err),
+        }
+    }
+
+    /// Open the config file for reading
+    ///
+    /// This bypasses the format layer, for manual IO like seeking or partial reads
+    pub fn open_read(&self) -> IoResult<File> {
+        self.open_file()
+    }
+
+    /// Create or truncate the config file for writing, creating its parent directory
+    ///
+    /// This bypasses the format layer, for manual IO like streaming writes
+    pub fn open_write(&self) -> IoResult<File> {
+        self.create_parent_dir()?;
+        self.retry(|| File::create(&self.path))
+    }
+
+    /// Read the entire contents of a file into a string
+    pub fn read_to_string(&self) -> IoResult<String> {
+        let bytes = self.read_to_vec()?;
+        String::from_utf8(bytes)
+            .map_err(|err| PathError::wrap(&self.path, IoError::new(ErrorKind::InvalidData, err)))
+    }
+
+    /// Read the entire contents of a file encoded as standard `base64`, surrounding whitespace is ignored
+    #[cfg(feature = "base64")]
+    pub fn read_base64(&self) -> IoResult<Vec<u8>> {
+        use base64::Engine;
+        let s = self.read_to_string()?;
+        base64::engine::general_purpose::STANDARD
+            .decode(s.trim())
+            .map_err(|err| PathError::wrap(&self.path, IoError::new(ErrorKind::InvalidData, err)))
+    }
+
+    /// Read the entire contents of a file into a string, without a leading UTF-8 BOM
+    ///
+    /// Some Windows tools start UTF-8 files with a BOM, the format methods skip it too.
+    pub fn read_to_string_strip_bom(&self) -> IoResult<String> {
+        let mut s = self.read_to_string()?;
+        if s.starts_with('\u{feff}') {
+            s.drain(..'\u{feff}'.len_utf8());
+        }
+        Ok(s)
+    }
+
+    /// Read the entire contents of a file into a string, failing with `TimedOut` after `dur`
+    ///
+    /// The read runs on a separate thread, which keeps running in the background
+    /// after a timeout until the underlying read returns.
+    pub fn read_to_string_timeout(&self, dur: Duration) -> IoResult<String> {
+        let (tx, rx) = mpsc::channel();
+        let config = self.clone();
+        thread::spawn(move || {
+            let _ = tx.send(config.read_to_string());
+        });
+        rx.recv_timeout(dur).unwrap_or_else(|_| {
+            Err(IoError::new(
+                ErrorKind::TimedOut,
+                format!("reading {} timed out", self.path.display()),
+            ))
+        })
+    }
+
+    /// Read the file into a string, or `None` if it has not been modified since the last call
+    ///
+    /// The modification time, the length and, on Unix, the inode are compared, the content
+    /// is kept in `cache`
+    pub fn read_to_string_cached(&self, cache: &mut ConfigCache) -> IoResult<Option<String>> {
+        let meta = self.path.metadata();
+        let version = meta
+            .and_then(|meta| FileVersion::of(&meta))
+            .map_err(|err| PathError::wrap(&self.path, err))?;
+        if cache.version == Some(version) {
+            return Ok(None);
+        }
+        cache.content = self.read_to_string()?;
+        cache.version = Some(version);
+        Ok(Some(cache.content.clone()))
+    }
+
+    /// Read the entire contents of a file into a `Vec<u8>`
+    pub fn read_to_vec(&self) -> IoResult<Vec<u8>> {
+        let f = self.open_file()?;
+        let mut buf = Vec::new();
+        self.read_all(f, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Parse the config file from `json` content
     #[cfg(feature = "json")]
-    pub fn save_json<T>(&self, data: T) -> Result<(), JsonError>
+    pub fn json<T>(&self) -> Result<T, JsonError>
     where
-        T: Serialize,
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_to_vec()?;
+        let bytes = non_empty(&bytes).ok_or_else(|| JsonError::Empty(self.path.clone()))?;
+        json_from_slice(bytes).map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Read the config file for parsing into types that borrow from it, see [`JsonReader`]
+    #[cfg(feature = "json")]
+    pub fn json_borrowed(&self) -> IoResult<JsonReader> {
+        Ok(JsonReader {
+            content: self.read_to_string()?,
+            path: self.path.clone(),
+        })
+    }
+
+    /// Parse the config file from `json` content with a stateful [`DeserializeSeed`](serde::de::DeserializeSeed)
+    #[cfg(feature = "json")]
+    pub fn json_seeded<S>(&self, seed: S) -> Result<S::Value, JsonError>
+    where
+        S: serde::de::DeserializeSeed<'static>,
+    {
+        let mut reader = self.limit_reader(self.open_file()?);
+        let result = {
+            // Reading a `File` directly would be a system call per byte
+            let reader = std::io::BufReader::new(&mut reader);
+            let mut de = serde_json::Deserializer::from_reader(reader);
+            deserialize_seed(seed, &mut de)
+                .and_then(|value| de.end().map(|()| value).map_err(SerdeError::from))
+        };
+        // Rather than the parse error of the cut off content
+        reader.check()?;
+        Ok(result.map_err(|err| err.in_file(&self.path))?)
+    }
+
+    /// Parse the config file from `json` content, decompressing it first if it is gzip-compressed
+    #[cfg(all(feature = "json", feature = "gzip"))]
+    pub fn json_auto<T>(&self) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_to_vec()?;
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut json = Vec::new();
+            self.limit_reader(flate2::read::GzDecoder::new(bytes.as_slice()))
+                .read_to_end(&mut json)
+                .map_err(|err| PathError::wrap(&self.path, err))?;
+            let json = non_empty(&json).ok_or_else(|| JsonError::Empty(self.path.clone()))?;
+            json_from_slice(json).map_err(|err| err.in_file(&self.path))
+        } else {
+            let bytes = non_empty(&bytes).ok_or_else(|| JsonError::Empty(self.path.clone()))?;
+            json_from_slice(bytes).map_err(|err| err.in_file(&self.path))
+        }
+    }
+
+    /// Parse the config file from `json lines` content, one `json` value per line
+    #[cfg(feature = "json")]
+    pub fn json_lines<T>(&self) -> Result<Vec<T>, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        self.json_lines_iter()?.collect()
+    }
+
+    /// Stream the config file from `json lines` content, one `json` value per line
+    #[cfg(feature = "json")]
+    pub fn json_lines_iter<T>(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<T, JsonError>>, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let f = self.limit_reader(self.open_file()?);
+        let lines = std::io::BufRead::lines(std::io::BufReader::new(f));
+        let path = self.path.clone();
+        Ok(lines.filter_map(move |line| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(json_from_slice(line.as_bytes()).map_err(|err| err.in_file(&path))),
+            Err(err) => Some(Err(JsonError::Io(PathError::wrap(&path, err)))),
+        }))
+    }
+
+    /// Parse the config file from `base64` encoded `json` content
+    #[cfg(all(feature = "json", feature = "base64"))]
+    pub fn json_base64<T>(&self) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_base64()?;
+        json_from_slice(&bytes).map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Parse the config file from `json` content with `//` and `/* */` comments
+    #[cfg(feature = "json")]
+    pub fn json_with_comments<T>(&self) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_to_vec()?;
+        let bytes = non_empty(&bytes).ok_or_else(|| JsonError::Empty(self.path.clone()))?;
+        let stripped = strip_json_comments(bytes);
+        json_from_slice(&stripped).map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Parse the `json` config file into `target`, which is left unchanged on errors
+    #[cfg(feature = "json")]
+    pub fn json_reload<T>(&self, target: &mut T) -> Result<(), JsonError>
+    where
+        T: DeserializeOwned,
     {
-        let bytes = serde_json::to_vec_pretty(&data).map_err(JsonError::Serde)?;
-        self.create_parent_dir().map_err(JsonError::Io)?;
-        fs::write(&self.path, &bytes).map_err(JsonError::Io)?;
+        *target = self.json()?;
         Ok(())
     }
 
-    /// Save struct to local file (`yaml` format)
-    #[cfg(feature = "yaml")]
-    pub fn save_yaml<T>(&self, data: T) -> Result<(), YamlError>
+    /// [`json_reload`](Self::json_reload) for a value shared between threads
+    ///
+    /// The file is parsed before locking, so other threads are only blocked for the swap.
+    /// A poisoned mutex is replaced too.
+    #[cfg(feature = "json")]
+    pub fn json_reload_arc<T>(
+        &self,
+        target: &std::sync::Arc<std::sync::Mutex<T>>,
+    ) -> Result<(), JsonError>
     where
-        T: Serialize,
+        T: DeserializeOwned,
     {
-        let bytes = serde_yaml::to_string(&data).map_err(YamlError::Serde)?;
-        self.create_parent_dir().map_err(YamlError::Io)?;
-        fs::write(&self.path, &bytes).map_err(YamlError::Io)?;
+        let value = self.json()?;
+        *target.lock().unwrap_or_else(|err| err.into_inner()) = value;
         Ok(())
     }
 
-    /// Save struct to local file (`toml` format)
-    #[cfg(feature = "toml")]
-    pub fn save_toml<T>(&self, data: T) -> Result<(), TomlSaveError>
+    /// Whether `current` differs from the value saved in the `json` config file
+    ///
+    /// The `json` values are compared, so keys in the file that `T` ignores are changes too,
+    /// as saving `current` would drop them.
+    #[cfg(feature = "json")]
+    pub fn json_has_unsaved_changes<T>(&self, current: &T) -> Result<bool, JsonError>
+    where
+        T: Serialize + DeserializeOwned + PartialEq,
+    {
+        let saved = self.json::<serde_json::Value>()?;
+        // Through the text, as it is saved, eg. an `f32` is not widened to a different `f64`
+        let s = Self::to_json_string(current).map_err(self.serde_error())?;
+        let current = serde_json::from_str::<serde_json::Value>(&s).map_err(self.serde_error())?;
+        Ok(saved != current)
+    }
+
+    /// Compare `proposed` to the value saved in the `json` config file, eg. to preview changes
+    ///
+    /// Returns `null` if nothing changed, otherwise `{"old": …, "new": …}` for a changed value.
+    /// Objects are compared key by key and only hold the changed keys, a missing key or file is `null`.
+    ///
+    /// eg. `{"server": {"port": {"old": 80, "new": 8080}}}`
+    #[cfg(feature = "json")]
+    pub fn json_diff_from_disk<T>(&self, proposed: &T) -> Result<serde_json::Value, JsonError>
     where
         T: Serialize,
     {
-        let bytes = toml::to_string_pretty(&data).map_err(TomlSaveError::Serde)?;
-        self.create_parent_dir().map_err(TomlSaveError::Io)?;
-        fs::write(&self.path, &bytes).map_err(TomlSaveError::Io)?;
+        let new = serde_json::to_value(proposed)?;
+        let old = match self.json() {
+            Ok(old) => old,
+            Err(err) if err.is_not_found() || err.is_empty_file() => serde_json::Value::Null,
+            Err(err) => return Err(err),
+        };
+        Ok(json_diff(old, new))
+    }
+
+    /// Parse the `json` config file into a map of dot separated key paths to values
+    ///
+    /// eg. `{"a": {"b": 1}, "c": 2}` is `{"a.b": 1, "c": 2}`.
+    /// Arrays and empty objects are kept as values, see [`json_unflatten`](Self::json_unflatten).
+    #[cfg(feature = "json")]
+    pub fn json_as_flat_map(
+        &self,
+    ) -> Result<std::collections::BTreeMap<String, serde_json::Value>, JsonError> {
+        let mut map = std::collections::BTreeMap::new();
+        flatten_json("", self.json()?, &mut map);
+        Ok(map)
+    }
+
+    /// Build nested `json` from a map of dot separated key paths, the inverse of
+    /// [`json_as_flat_map`](Self::json_as_flat_map)
+    ///
+    /// A value with a key path below it, eg. `a` with `a.b`, is replaced by an object.
+    #[cfg(feature = "json")]
+    pub fn json_unflatten(
+        map: &std::collections::BTreeMap<String, serde_json::Value>,
+    ) -> serde_json::Value {
+        use serde_json::{Map, Value};
+
+        let mut root = Map::new();
+        for (path, value) in map {
+            let mut keys = path.split('.');
+            let last = keys.next_back().unwrap_or_default();
+            let mut object = &mut root;
+            for key in keys {
+                let entry = object
+                    .entry(key)
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if !entry.is_object() {
+                    *entry = Value::Object(Map::new());
+                }
+                object = entry.as_object_mut().unwrap();
+            }
+            object.insert(last.to_string(), value.clone());
+        }
+        Value::Object(root)
+    }
+
+    /// Parse a base `json` config with an override config merged onto it
+    ///
+    /// Objects are merged key by key, other values are replaced. A missing or empty override is skipped.
+    ///
+    /// eg. `config.json` with `config.prod.json`
+    #[cfg(feature = "json")]
+    pub fn layered_json<T>(base: &HomeConfig, override_: &HomeConfig) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut value = base.json::<serde_json::Value>()?;
+        match override_.json() {
+            Ok(layer) => merge_json(&mut value, layer),
+            Err(err) if err.is_not_found() || err.is_empty_file() => {}
+            Err(err) => return Err(err),
+        }
+        Ok(deserialize(value)?)
+    }
+
+    /// Parse the `json` config file, replacing `"$include"` keys with the content of the referenced files
+    ///
+    /// The value is a path or an array of paths, relative to the directory of the including file,
+    /// eg. `{"$include": "server.json", "name": "app"}`. Included objects are merged in order,
+    /// then the other keys of the including object are merged onto them. Included files can
+    /// include other files, an include cycle is an [`InvalidData`](ErrorKind::InvalidData) error.
+    #[cfg(feature = "json")]
+    pub fn json_with_includes<T>(&self) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut value = self.json()?;
+        self.resolve_json_includes(&mut value, &mut HashSet::new())?;
+        Ok(deserialize(value).map_err(|err| err.in_file(&self.path))?)
+    }
+
+    // `visited` holds the files being included, not all the files seen, so a file can be included twice
+    #[cfg(feature = "json")]
+    fn resolve_json_includes(
+        &self,
+        value: &mut serde_json::Value,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<(), JsonError> {
+        let path = self.canonical_path()?;
+        if !visited.insert(path.clone()) {
+            let err = IoError::new(ErrorKind::InvalidData, "include cycle");
+            return Err(JsonError::Io(PathError::wrap(&self.path, err)));
+        }
+        let result = self.resolve_json_value_includes(value, visited);
+        visited.remove(&path);
+        result
+    }
+
+    #[cfg(feature = "json")]
+    fn resolve_json_value_includes(
+        &self,
+        value: &mut serde_json::Value,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<(), JsonError> {
+        use serde_json::Value;
+
+        let object = match value {
+            Value::Object(object) => object,
+            Value::Array(array) => {
+                for value in array {
+                    self.resolve_json_value_includes(value, visited)?;
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        for value in object.values_mut() {
+            self.resolve_json_value_includes(value, visited)?;
+        }
+        let paths = match object.remove("$include") {
+            None => return Ok(()),
+            Some(Value::String(path)) => vec![path],
+            Some(Value::Array(paths)) => paths
+                .into_iter()
+                .map(|path| match path {
+                    Value::String(path) => Ok(path),
+                    _ => Err(serde::de::Error::custom(
+                        "`$include` must be a path or paths",
+                    )),
+                })
+                .collect::<Result<_, serde_json::Error>>()
+                .map_err(self.serde_error())?,
+            Some(_) => {
+                let err: serde_json::Error =
+                    serde::de::Error::custom("`$include` must be a path or paths");
+                return Err(self.serde_error()(err).into());
+            }
+        };
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        let mut merged = Value::Object(serde_json::Map::new());
+        for path in paths {
+            let include = self.sibling(dir.join(path));
+            let mut included = include.json()?;
+            include.resolve_json_includes(&mut included, visited)?;
+            merge_json(&mut merged, included);
+        }
+        merge_json(&mut merged, std::mem::take(value));
+        *value = merged;
         Ok(())
     }
 
-    /// Save struct to local file (`hcl` format)
-    #[cfg(feature = "hcl")]
-    pub fn save_hcl<T>(&self, data: T) -> Result<(), HclError>
+    /// Add the keys of `defaults` missing from the `json` config file, keeping existing values
+    ///
+    /// Nested objects are filled recursively, a missing or empty file is created from `defaults`.
+    #[cfg(feature = "json")]
+    pub fn set_json_defaults<T>(&self, defaults: &T) -> Result<(), JsonError>
     where
         T: Serialize,
     {
-        let bytes = hcl::to_vec(&data).map_err(HclError::Serde)?;
-        self.create_parent_dir().map_err(HclError::Io)?;
-        fs::write(&self.path, &bytes).map_err(HclError::Io)?;
-        Ok(())
+        let defaults = serde_json::to_value(defaults)?;
+        let value = match self.json() {
+            Ok(mut value) => {
+                fill_json_defaults(&mut value, defaults);
+                value
+            }
+            Err(err) if err.is_not_found() || err.is_empty_file() => defaults,
+            Err(err) => return Err(err),
+        };
+        self.save_json(value)
+    }
+
+    /// Parse and merge several `json` config files, later layers override earlier ones
+    ///
+    /// Objects are merged key by key, other values are replaced. Missing and empty files are skipped.
+    ///
+    /// eg. `HomeConfig::parse_and_merge_json::<T>(&[&system, &user, &local])`
+    #[cfg(feature = "json")]
+    pub fn parse_and_merge_json<T>(layers: &[&HomeConfig]) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut merged = serde_json::Value::Object(Default::default());
+        for layer in layers {
+            match layer.json() {
+                Ok(value) => merge_json(&mut merged, value),
+                Err(err) if err.is_not_found() || err.is_empty_file() => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(deserialize(merged)?)
+    }
+
+    /// Parse the config file from `yaml` content
+    #[cfg(feature = "yaml")]
+    pub fn yaml<T>(&self) -> Result<T, YamlError>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_to_vec()?;
+        let bytes = non_empty(&bytes).ok_or_else(|| YamlError::Empty(self.path.clone()))?;
+        yaml_from_slice(bytes).map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Parse the config file from `toml` content
+    #[cfg(feature = "toml")]
+    pub fn toml<T>(&self) -> Result<T, TomlParseError>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_to_vec()?;
+        let bytes = non_empty(&bytes).ok_or_else(|| TomlParseError::Empty(self.path.clone()))?;
+        toml_from_slice(bytes).map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Parse a base `toml` config with an override config merged onto it
+    ///
+    /// Tables are merged key by key, other values are replaced. A missing or empty override is skipped.
+    ///
+    /// eg. `config.toml` with `config.prod.toml`
+    #[cfg(feature = "toml")]
+    pub fn layered_toml<T>(base: &HomeConfig, override_: &HomeConfig) -> Result<T, TomlParseError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut value = base.toml::<toml::Value>()?;
+        match override_.toml() {
+            Ok(layer) => merge_toml(&mut value, layer),
+            Err(err) if err.is_not_found() || err.is_empty_file() => {}
+            Err(err) => return Err(err),
+        }
+        Ok(deserialize(value)?)
+    }
+
+    /// Parse the config file from `hcl` content
+    #[cfg(feature = "hcl")]
+    pub fn hcl<T>(&self) -> Result<T, HclError>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.read_to_vec()?;
+        let bytes = non_empty(&bytes).ok_or_else(|| HclError::Empty(self.path.clone()))?;
+        hcl_from_slice(bytes).map_err(|err| err.in_file(&self.path))
+    }
+
+    /// Read the entire contents of a file into a string, replacing invalid UTF-8 with `U+FFFD`
+    ///
+    /// Also returns whether anything was replaced, eg. to warn the user.
+    pub fn read_to_string_lossy(&self) -> IoResult<(String, bool)> {
+        Ok(match String::from_utf8(self.read_to_vec()?) {
+            Ok(s) => (s, false),
+            Err(err) => (String::from_utf8_lossy(err.as_bytes()).into_owned(), true),
+        })
+    }
+
+    /// Read the entire contents of a file into a string, `None` if it does not exist
+    ///
+    /// A missing parent directory is the same as a missing file, other errors are kept.
+    pub fn read_to_string_opt(&self) -> IoResult<Option<String>> {
+        optional(self.read_to_string(), |err| {
+            err.kind() == ErrorKind::NotFound
+        })
+    }
+
+    /// Read the entire contents of a file into a `Vec<u8>`, `None` if it does not exist
+    pub fn read_to_vec_opt(&self) -> IoResult<Option<Vec<u8>>> {
+        optional(self.read_to_vec(), |err| err.kind() == ErrorKind::NotFound)
+    }
+
+    /// [`json`](Self::json), `None` if the file does not exist
+    #[cfg(feature = "json")]
+    pub fn json_opt<T>(&self) -> Result<Option<T>, JsonError>
+    where
+        T: DeserializeOwned,
+    {
+        optional(self.json(), JsonError::is_not_found)
+    }
+
+    /// [`yaml`](Self::yaml), `None` if the file does not exist
+    #[cfg(feature = "yaml")]
+    pub fn yaml_opt<T>(&self) -> Result<Option<T>, YamlError>
+    where
+        T: DeserializeOwned,
+    {
+        optional(self.yaml(), YamlError::is_not_found)
+    }
+
+    /// [`toml`](Self::toml), `None` if the file does not exist
+    #[cfg(feature = "toml")]
+    pub fn toml_opt<T>(&self) -> Result<Option<T>, TomlParseError>
+    where
+        T: DeserializeOwned,
+    {
+        optional(self.toml(), TomlParseError::is_not_found)
+    }
+
+    /// [`hcl`](Self::hcl), `None` if the file does not exist
+    #[cfg(feature = "hcl")]
+    pub fn hcl_opt<T>(&self) -> Result<Option<T>, HclError>
+    where
+        T: DeserializeOwned,
+    {
+        optional(self.hcl(), HclError::is_not_found)
+    }
+
+    /// Serialize struct to a `json` string, as written by [`save_json`](Self::save_json)
+    #[cfg(feature = "json")]
+    pub fn to_json_string<T>(data: &T) -> Result<String, serde_json::Error>
+    where
+        T: Serialize,
+    {
+        serde_json::to_string_pretty(data)
+    }
+
+    /// Serialize struct to a `yaml` string, as written by [`save_yaml`](Self::save_yaml)
+    ///
+    /// Errors for values that `yaml` cannot represent, see [`save_yaml`](Self::save_yaml)
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml_string<T>(data: &T) -> Result<String, serde_yaml::Error>
+    where
+        T: Serialize,
+    {
+        serde_yaml::to_string(data).map_err(|err| {
+            serde::ser::Error::custom(format!("value cannot be represented in yaml: {}", err))
+        })
+    }
+
+    /// Serialize struct to a `toml` string, as written by [`save_toml`](Self::save_toml)
+    #[cfg(feature = "toml")]
+    pub fn to_toml_string<T>(data: &T) -> Result<String, toml::ser::Error>
+    where
+        T: Serialize,
+    {
+        match toml::to_string_pretty(data) {
+            Ok(s) => Ok(s),
+            // Going through `toml::Value` emits plain values before tables,
+            // whatever the field order of `T`
+            Err(_) => toml::to_string_pretty(&toml::Value::try_from(data)?),
+        }
+    }
+
+    /// Serialize struct to a `hcl` string, as written by [`save_hcl`](Self::save_hcl)
+    #[cfg(feature = "hcl")]
+    pub fn to_hcl_string<T>(data: &T) -> Result<String, hcl::Error>
+    where
+        T: Serialize,
+    {
+        hcl::to_string(data)
+    }
+
+    /// Parse struct from a `json` string
+    #[cfg(feature = "json")]
+    pub fn from_json_str<T>(s: &str) -> Result<T, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_str(s)
+    }
+
+    /// Parse struct from a `yaml` string
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str<T>(s: &str) -> Result<T, serde_yaml::Error>
+    where
+        T: DeserializeOwned,
+    {
+        serde_yaml::from_str(s)
+    }
+
+    /// Parse struct from a `toml` string
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str<T>(s: &str) -> Result<T, toml::de::Error>
+    where
+        T: DeserializeOwned,
+    {
+        toml::from_str(s)
+    }
+
+    /// Parse struct from a `hcl` string
+    #[cfg(feature = "hcl")]
+    pub fn from_hcl_str<T>(s: &str) -> Result<T, hcl::Error>
+    where
+        T: DeserializeOwned,
+    {
+        hcl::from_str(s)
+    }
+
+    fn create_parent_dir(&self) -> IoResult<()> {
+        if !self.path.exists() {
+            if let Some(parent) = self.path.parent() {
+                self.retry(|| fs::create_dir_all(parent))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Save content to local file
+    pub fn save<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+        self.create_parent_dir()?;
+        self.write_file(data.as_ref())
+    }
+
+    /// Save content to `file_name` in the directory of the config file, eg. for a snapshot
+    ///
+    /// `file_name` must be a plain file name, eg. `config.2024.json`, otherwise the error is `InvalidInput`.
+    pub fn save_as<T: AsRef<[u8]>>(&self, file_name: &str, data: T) -> IoResult<()> {
+        let mut components = Path::new(file_name).components();
+        match (components.next(), components.next()) {
+            (Some(std::path::Component::Normal(_)), None) => {}
+            _ => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid file name {:?}", file_name),
+                ))
+            }
+        }
+        self.sibling(self.path.with_file_name(file_name)).save(data)
+    }
+
+    /// Save text content to local file
+    ///
+    /// Same as [`save`](Self::save), but only accepts valid UTF-8,
+    /// so the file can always be read back with [`read_to_string`](Self::read_to_string).
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "config.toml");
+    /// config.save_text("name = \"app\"").unwrap();
+    ///
+    /// // Any bytes are accepted by `save`
+    /// config.save(b"name = \"\xFF\"").unwrap();
+    /// ```
+    ///
+    /// Bytes do not compile with `save_text`:
+    ///
+    /// ```compile_fail
+    /// use home_config::HomeConfig;
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "config.toml");
+    /// config.save_text(b"name = \"\xFF\"").unwrap();
+    /// ```
+    pub fn save_text<S: AsRef<str>>(&self, s: S) -> IoResult<()> {
+        self.save(s.as_ref())
+    }
+
+    /// Save content to local file, encoded as standard `base64`
+    #[cfg(feature = "base64")]
+    pub fn save_base64<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+        use base64::Engine;
+        self.save(base64::engine::general_purpose::STANDARD.encode(data))
+    }
+
+    /// Save content to local file, returning the previous content or `None` if it did not exist
+    ///
+    /// eg. for a single step undo
+    pub fn save_returning_previous<T: AsRef<[u8]>>(&self, data: T) -> IoResult<Option<Vec<u8>>> {
+        let previous = match self.read_to_vec() {
+            Ok(bytes) => Some(bytes),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        self.save(data)?;
+        Ok(previous)
+    }
+
+    /// Write content to a new temporary file next to the config, to replace it later
+    ///
+    /// Nothing changes until the returned file is persisted with
+    /// [`persist(config.path())`](tempfile::NamedTempFile::persist), an atomic rename.
+    /// For an all-or-nothing update of several configs, stage every file first and only
+    /// persist once all writes succeeded. Dropping the handle deletes the temporary file.
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    ///
+    /// let app = HomeConfig::with_config_dir("app", "app.toml");
+    /// let keys = HomeConfig::with_config_dir("app", "keys.toml");
+    /// let staged = [(app.stage("a = 1").unwrap(), &app), (keys.stage("b = 2").unwrap(), &keys)];
+    /// for (file, config) in staged {
+    ///     file.persist(config.path()).unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "tempfile")]
+    pub fn stage<T: AsRef<[u8]>>(&self, data: T) -> IoResult<tempfile::NamedTempFile> {
+        self.create_parent_dir()?;
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let mut prefix = OsString::from(".");
+        prefix.push(self.path.file_name().unwrap_or_default());
+        self.retry(|| {
+            // Ends with `.tmp`, so directory watches skip it, see `is_temp_file`
+            let mut file = tempfile::Builder::new()
+                .prefix(&prefix)
+                .suffix(".tmp")
+                .tempfile_in(dir)?;
+            file.write_all(data.as_ref())?;
+            Ok(file)
+        })
+    }
+
+    /// Save content by truncating the existing file in place, creating it if needed
+    ///
+    /// The file keeps its inode, so watches and open handles on it see the change.
+    pub fn truncate_and_rewrite<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+        self.create_parent_dir()?;
+        self.retry(|| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&self.path)?
+                .write_all(data.as_ref())
+        })
+    }
+
+    /// Save content to local file and flush it to the storage device, see [`sync`](Self::sync)
+    pub fn save_synced<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+        self.save(data)?;
+        self.sync()
+    }
+
+    /// Flush the config file to the storage device
+    ///
+    /// On unix the parent directory is flushed too, so a newly created file survives a crash.
+    /// Network file systems may still acknowledge before the data is stored.
+    pub fn sync(&self) -> IoResult<()> {
+        self.retry(|| {
+            // Only Windows needs write access to flush, read-only files work elsewhere
+            OpenOptions::new()
+                .read(true)
+                .write(cfg!(windows))
+                .open(&self.path)?
+                .sync_all()?;
+            #[cfg(unix)]
+            if let Some(parent) = self.path.parent() {
+                File::open(parent)?.sync_all()?;
+            }
+            Ok(())
+        })
+    }
+
+    fn create_secret_parent_dir(&self) -> IoResult<()> {
+        if let Some(parent) = self.path.parent() {
+            let mut builder = fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            self.retry(|| builder.create(parent))?;
+        }
+        Ok(())
+    }
+
+    fn write_secret_file(&self, data: &[u8]) -> IoResult<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut f = self.retry(|| options.open(&self.path))?;
+        let result = (|| {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if f.metadata()?.permissions().mode() & 0o077 != 0 {
+                    f.set_permissions(fs::Permissions::from_mode(0o600))?;
+                }
+            }
+            f.write_all(data)
+        })();
+        result.map_err(|err| PathError::wrap(&self.path, err))
+    }
+
+    /// Save content to local file, readable and writable only by the current user
+    ///
+    /// On Unix the file is created with mode `0600` and missing parent directories with `0700`,
+    /// an existing file with looser permissions is tightened to `0600` before writing.
+    /// On Windows the file inherits the default ACL of its directory.
+    pub fn save_secret<T: AsRef<[u8]>>(&self, data: T) -> IoResult<()> {
+        self.create_secret_parent_dir()?;
+        self.write_secret_file(data.as_ref())
+    }
+
+    /// Save struct to local file (`json` format)
+    #[cfg(feature = "json")]
+    pub fn save_json<T>(&self, data: T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_json_string(&data).map_err(self.serde_error())?;
+        self.create_parent_dir()?;
+        self.write_file(bytes.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`json` format), serializing straight into the file
+    ///
+    /// Unlike [`save_json`](Self::save_json), the output is never held in memory as a whole,
+    /// for large configs. It is written to a temporary `.<name>.<pid>.<n>.tmp` file next to
+    /// the config, flushed to the storage device and renamed over the config when complete,
+    /// so readers never see a partial file. An existing file keeps its permissions.
+    #[cfg(feature = "json")]
+    pub fn save_json_streaming<T>(&self, data: T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        self.replace_file(|writer| {
+            serde_json::to_writer_pretty(writer, &data).map_err(self.serde_error())?;
+            Ok(())
+        })
+    }
+
+    /// Save records to local file (`json lines` format), one compact `json` value per line
+    #[cfg(feature = "json")]
+    pub fn save_json_lines<T, I>(&self, records: I) -> Result<(), JsonError>
+    where
+        T: Serialize,
+        I: IntoIterator<Item = T>,
+    {
+        let mut bytes = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut bytes, &record).map_err(self.serde_error())?;
+            bytes.push(b'\n');
+        }
+        self.create_parent_dir()?;
+        self.write_file(&bytes)?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`json` format), failing if it is not a json object
+    #[cfg(feature = "json")]
+    pub fn save_json_object<T>(&self, data: T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_json_string(&data).map_err(self.serde_error())?;
+        if bytes.trim_start().as_bytes().first() != Some(&b'{') {
+            let err: serde_json::Error =
+                serde::ser::Error::custom("expected a json object at the top level");
+            return Err(self.serde_error()(err).into());
+        }
+        self.create_parent_dir()?;
+        self.write_file(bytes.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`json` format) with a `$schema` key first, for editor completion
+    ///
+    /// The other keys keep their order. Fails if it is not a json object, an existing
+    /// `$schema` key is replaced.
+    #[cfg(feature = "json")]
+    pub fn save_json_with_schema<T>(&self, data: T, schema_url: &str) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        // Spliced into the serialized entries, the keys of a `json` value are sorted
+        let s = Self::to_json_string(&data).map_err(self.serde_error())?;
+        let JsonEntries(mut entries) = serde_json::from_str(&s).map_err(self.serde_error())?;
+        entries.retain(|(key, _)| key != "$schema");
+        let schema = serde_json::to_string(schema_url).map_err(self.serde_error())?;
+        let schema = RawValue::from_string(schema).map_err(self.serde_error())?;
+        entries.insert(0, ("$schema".to_string(), schema));
+        let s = Self::to_json_string(&JsonEntries(entries)).map_err(self.serde_error())?;
+        self.save(s)?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`json` format), see [`save_secret`](Self::save_secret)
+    #[cfg(feature = "json")]
+    pub fn save_json_secret<T>(&self, data: T) -> Result<(), JsonError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_json_string(&data).map_err(self.serde_error())?;
+        self.create_secret_parent_dir()?;
+        self.write_secret_file(bytes.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`yaml` format)
+    ///
+    /// Non-string map keys, like `HashMap<u32, String>`, are saved as `yaml` keys of that type.
+    /// Values that `yaml` cannot represent, like nested enums, fail with a
+    /// `value cannot be represented in yaml` error.
+    #[cfg(feature = "yaml")]
+    pub fn save_yaml<T>(&self, data: T) -> Result<(), YamlError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_yaml_string(&data).map_err(self.serde_error())?;
+        self.create_parent_dir()?;
+        self.write_file(bytes.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`yaml` format), starting with the `---` document marker
+    #[cfg(feature = "yaml")]
+    pub fn save_yaml_with_marker<T>(&self, data: T) -> Result<(), YamlError>
+    where
+        T: Serialize,
+    {
+        let yaml = Self::to_yaml_string(&data).map_err(self.serde_error())?;
+        self.create_parent_dir()?;
+        self.write_file(format!("---\n{}", yaml).as_bytes())?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`toml` format)
+    #[cfg(feature = "toml")]
+    pub fn save_toml<T>(&self, data: T) -> Result<(), TomlSaveError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_toml_string(&data).map_err(self.serde_error())?;
+        self.create_parent_dir()?;
+        self.write_file(bytes.as_bytes())?;
+        Ok(())
+    }
+
+    /// Get the length of the array at `key`, eg. the number of `[[key]]` tables
+    ///
+    /// A `toml` document is always a table, so arrays live under a key. A missing key has length 0.
+    #[cfg(feature = "toml")]
+    pub fn toml_array_len(&self, key: &str) -> Result<usize, TomlParseError> {
+        let table = self.toml::<toml::value::Table>()?;
+        match table.get(key) {
+            Some(toml::Value::Array(array)) => Ok(array.len()),
+            Some(_) => {
+                let err: toml::de::Error =
+                    serde::de::Error::custom(format!("`{}` is not an array", key));
+                Err(self.serde_error()(err).into())
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Add `value` to the end of the array at `key`, creating the file and the array if needed
+    ///
+    /// Tables are saved as `[[key]]` sections. The file is rewritten, comments are lost.
+    /// Errors are parse errors when the existing file is broken and save errors otherwise.
+    #[cfg(feature = "toml")]
+    pub fn toml_array_append<T>(&self, key: &str, value: T) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        let value = toml::Value::try_from(value)
+            .map_err(self.serde_error())
+            .map_err(Error::TomlSer)?;
+        self.update_toml_array(key, |array| array.push(value))
+    }
+
+    /// Add `value` to the start of the array at `key`, see [`toml_array_append`](Self::toml_array_append)
+    #[cfg(feature = "toml")]
+    pub fn toml_array_prepend<T>(&self, key: &str, value: T) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        let value = toml::Value::try_from(value)
+            .map_err(self.serde_error())
+            .map_err(Error::TomlSer)?;
+        self.update_toml_array(key, |array| array.insert(0, value))
+    }
+
+    #[cfg(feature = "toml")]
+    fn update_toml_array<F>(&self, key: &str, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Vec<toml::Value>),
+    {
+        let mut table = match self.toml::<toml::value::Table>() {
+            Ok(table) => table,
+            Err(err) if err.is_not_found() || err.is_empty_file() => Default::default(),
+            Err(err) => return Err(err.into()),
+        };
+        let array = table
+            .entry(key)
+            .or_insert_with(|| toml::Value::Array(Vec::new()));
+        match array {
+            toml::Value::Array(array) => f(array),
+            _ => {
+                let err: toml::ser::Error =
+                    serde::ser::Error::custom(format!("`{}` is not an array", key));
+                return Err(Error::TomlSer(self.serde_error()(err)));
+            }
+        }
+        Ok(self.save_toml(table)?)
+    }
+
+    /// Save struct to local file (`toml` format), rendering the tables at `inline_keys` inline
+    ///
+    /// eg. `server = { host = "localhost", port = 8080 }` instead of a `[server]` section.
+    /// Keys are dotted paths, tables nested in an inline table are inline too.
+    /// Paths to anything else than a table, such as an array of tables, are left as is.
+    #[cfg(feature = "toml")]
+    pub fn save_toml_inline<T>(&self, data: T, inline_keys: &[&str]) -> Result<(), TomlSaveError>
+    where
+        T: Serialize,
+    {
+        let s = Self::to_toml_string(&data).map_err(self.serde_error())?;
+        let mut doc = s
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+        for key in inline_keys {
+            inline_toml_table(doc.as_table_mut(), key);
+        }
+        self.create_parent_dir()?;
+        self.write_file(doc.to_string().as_bytes())?;
+        Ok(())
+    }
+
+    /// Save struct to local file (`hcl` format)
+    #[cfg(feature = "hcl")]
+    pub fn save_hcl<T>(&self, data: T) -> Result<(), HclError>
+    where
+        T: Serialize,
+    {
+        let bytes = Self::to_hcl_string(&data).map_err(self.serde_error())?;
+        self.create_parent_dir()?;
+        self.write_file(bytes.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save content to local file only if it does not exist yet
+    ///
+    /// Returns `true` if the content was written. It is written to a temporary file
+    /// first and linked into place when complete, so a failed write never leaves a
+    /// partial file behind.
+    pub fn install_default<T: AsRef<[u8]>>(&self, content: T) -> IoResult<bool> {
+        self.write_temp_file(false, |writer| {
+            writer
+                .write_all(content.as_ref())
+                .map_err(|err| PathError::wrap(&self.path, err))
+        })
+    }
+
+    /// Save struct to local file (`json` format) only if it does not exist yet
+    #[cfg(feature = "json")]
+    pub fn install_default_json<T>(&self, default: &T) -> Result<bool, JsonError>
+    where
+        T: Serialize,
+    {
+        let s = Self::to_json_string(default).map_err(self.serde_error())?;
+        self.install_default(s).map_err(JsonError::Io)
+    }
+
+    /// Save struct to local file (`yaml` format) only if it does not exist yet
+    #[cfg(feature = "yaml")]
+    pub fn install_default_yaml<T>(&self, default: &T) -> Result<bool, YamlError>
+    where
+        T: Serialize,
+    {
+        let s = Self::to_yaml_string(default).map_err(self.serde_error())?;
+        self.install_default(s).map_err(YamlError::Io)
+    }
+
+    /// Save struct to local file (`toml` format) only if it does not exist yet
+    #[cfg(feature = "toml")]
+    pub fn install_default_toml<T>(&self, default: &T) -> Result<bool, TomlSaveError>
+    where
+        T: Serialize,
+    {
+        let s = Self::to_toml_string(default).map_err(self.serde_error())?;
+        self.install_default(s).map_err(TomlSaveError::Io)
+    }
+
+    /// Save content to a new timestamped file next to the config, see [`with_timestamp_suffix`](Self::with_timestamp_suffix)
+    pub fn save_timestamped<T: AsRef<[u8]>>(&self, data: T) -> IoResult<HomeConfig> {
+        let config = self.with_timestamp_suffix();
+        config.save(data)?;
+        Ok(config)
+    }
+
+    /// Create an empty file if it does not exist, otherwise update its modification time
+    pub fn touch(&self) -> IoResult<()> {
+        self.create_parent_dir()?;
+        let f = self.retry(|| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+        })?;
+        f.set_modified(SystemTime::now())
+    }
+
+    /// Rename the config file within the same directory
+    ///
+    /// An existing file at the destination is replaced on Unix
+    pub fn rename<P: AsRef<Path>>(&self, new_name: P) -> IoResult<HomeConfig> {
+        let path = match self.path.parent() {
+            Some(parent) => parent.join(new_name),
+            None => new_name.as_ref().to_path_buf(),
+        };
+        self.retry(|| fs::rename(&self.path, &path))?;
+        Ok(self.sibling(path))
+    }
+
+    /// Create a hard link at `dest` to the config file, so both paths share the same file
+    ///
+    /// `dest` must not exist, its parent directory is created. [`save`](Self::save) writes
+    /// in place and is seen through both paths, saves that replace the file, eg.
+    /// [`save_json_streaming`](Self::save_json_streaming), leave the other path with the old content.
+    pub fn create_hardlink(&self, dest: &HomeConfig) -> IoResult<()> {
+        dest.create_parent_dir()?;
+        dest.retry(|| fs::hard_link(&self.path, &dest.path))
+    }
+
+    /// Get the number of hard links to the config file, `1` if it has no other path
+    #[cfg(unix)]
+    pub fn link_count(&self) -> IoResult<u64> {
+        use std::os::unix::fs::MetadataExt;
+
+        let meta = fs::metadata(&self.path).map_err(|err| PathError::wrap(&self.path, err))?;
+        Ok(meta.nlink())
+    }
+
+    /// Delete the config file
+    pub fn delete(&self) -> IoResult<()> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(PathError::wrap(&self.path, err)),
+        }
+    }
+
+    /// Delete the directory containing the config file, with everything in it
+    ///
+    /// eg. `/home/name/.config/app`
+    ///
+    /// Only a directory under `~/.config` is deleted, for other configs, eg. from
+    /// [`with_file`](Self::with_file) whose directory is the home directory, it fails
+    /// with `InvalidInput`.
+    pub fn delete_config_dir(&self) -> IoResult<()> {
+        let config_dir = home_dir().join(".config");
+        let parent = match self.path.parent() {
+            Some(parent)
+                if parent != config_dir
+                    && parent.starts_with(&config_dir)
+                    && !parent
+                        .components()
+                        .any(|c| matches!(c, std::path::Component::ParentDir)) =>
+            {
+                parent
+            }
+            _ => {
+                let err = IoError::new(
+                    ErrorKind::InvalidInput,
+                    "the config directory is not under ~/.config",
+                );
+                return Err(PathError::wrap(&self.path, err));
+            }
+        };
+        match fs::remove_dir_all(parent) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(PathError::wrap(&self.path, err)),
+        }
+    }
+
+    /// [`delete_config_dir`](Self::delete_config_dir), only if `confirm` is `true`
+    ///
+    /// Fails with `PermissionDenied` otherwise
+    pub fn delete_config_dir_confirm(&self, confirm: bool) -> IoResult<()> {
+        if !confirm {
+            return Err(IoError::new(
+                ErrorKind::PermissionDenied,
+                "deleting the config directory was not confirmed",
+            ));
+        }
+        self.delete_config_dir()
+    }
+}
+
+impl fmt::Display for HomeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.path.display().fmt(f)
+    }
+}
+
+/// Configs are compared by path as given, without touching the file system
+///
+/// Other settings, eg. [`with_retry`](HomeConfig::with_retry), are ignored.
+/// See [`same_file`](HomeConfig::same_file) to compare the files.
+impl PartialEq for HomeConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for HomeConfig {}
+
+impl std::hash::Hash for HomeConfig {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+    }
+}
+
+impl PartialOrd for HomeConfig {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HomeConfig {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.path.cmp(&other.path)
+    }
+}
+
+/// Serialized as the path string, with the `serde` feature
+///
+/// Fails for a path that is not valid UTF-8. Other settings, eg. [`with_retry`](HomeConfig::with_retry),
+/// are not kept.
+#[cfg(feature = "serde")]
+impl serde::Serialize for HomeConfig {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.path.serialize(serializer)
+    }
+}
+
+/// Deserialized from a path string, with the `serde` feature
+///
+/// The path is used verbatim, unlike [`with_file`](HomeConfig::with_file) it is not joined to
+/// the home directory, as a serialized path is already absolute.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for HomeConfig {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            path: PathBuf::deserialize(deserializer)?,
+            retry: None,
+            max_size: None,
+        })
+    }
+}
+
+/// Pass a config to functions accepting a path
+///
+/// ```no_run
+/// use home_config::HomeConfig;
+///
+/// let config = HomeConfig::with_file("test.json");
+/// let len = std::fs::metadata(&config).unwrap().len();
+/// ```
+impl AsRef<Path> for HomeConfig {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<OsStr> for HomeConfig {
+    fn as_ref(&self) -> &OsStr {
+        self.path.as_ref()
+    }
+}
+
+impl From<HomeConfig> for OsString {
+    fn from(config: HomeConfig) -> Self {
+        config.path.into_os_string()
+    }
+}
+
+/// Use the path verbatim, unlike [`with_file`](HomeConfig::with_file) it is not joined to the home directory
+///
+/// ```
+/// use home_config::HomeConfig;
+/// use std::path::PathBuf;
+///
+/// let config = HomeConfig::from(PathBuf::from("/etc/app/config.json"));
+/// assert_eq!(config.path(), &PathBuf::from("/etc/app/config.json"));
+///
+/// // A relative path stays relative to the current directory
+/// let config = HomeConfig::from(PathBuf::from("config.json"));
+/// assert_eq!(config.path(), &PathBuf::from("config.json"));
+/// ```
+impl From<PathBuf> for HomeConfig {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            retry: None,
+            max_size: None,
+        }
+    }
+}
+
+/// Use the path verbatim, like `From<PathBuf>`
+///
+/// ```
+/// use home_config::HomeConfig;
+/// use std::path::Path;
+///
+/// let config = HomeConfig::from(Path::new("/etc/app/config.json"));
+/// assert_eq!(config.path(), Path::new("/etc/app/config.json"));
+/// ```
+impl From<&Path> for HomeConfig {
+    fn from(path: &Path) -> Self {
+        Self::from(path.to_path_buf())
+    }
+}
+
+/// Parse a path as written by a user, eg. in a command line argument
+///
+/// A leading `~` is the home directory and other paths must be absolute,
+/// so a relative path does not silently resolve against the current directory.
+/// Fails with `InvalidInput` otherwise, `~user` is not supported.
+///
+/// ```
+/// use home_config::HomeConfig;
+/// use std::path::Path;
+///
+/// let config: HomeConfig = "~/.config/app/config.json".parse().unwrap();
+/// assert_eq!(config.display_path(), "~/.config/app/config.json");
+///
+/// let config: HomeConfig = "/etc/app/config.json".parse().unwrap();
+/// assert_eq!(config.path(), Path::new("/etc/app/config.json"));
+///
+/// assert!("config.json".parse::<HomeConfig>().is_err());
+/// ```
+impl std::str::FromStr for HomeConfig {
+    type Err = IoError;
+
+    fn from_str(s: &str) -> IoResult<Self> {
+        let invalid = |reason: &str| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("invalid config path {:?}, {}", s, reason),
+            )
+        };
+        let path = match s.strip_prefix('~') {
+            Some("") => home_dir(),
+            Some(rest) => match rest.strip_prefix(std::path::is_separator) {
+                Some(rest) => home_dir().join(rest),
+                None => return Err(invalid("`~user` is not supported")),
+            },
+            None if Path::new(s).is_absolute() => PathBuf::from(s),
+            None => return Err(invalid("it must be absolute or start with `~`")),
+        };
+        Ok(Self::from(path))
+    }
+}
+
+/// Same as [`str::parse`], see `FromStr`
+///
+/// ```
+/// use home_config::HomeConfig;
+///
+/// let config = HomeConfig::try_from("~/.apprc").unwrap();
+/// assert_eq!(config.display_path(), "~/.apprc");
+/// ```
+impl TryFrom<&str> for HomeConfig {
+    type Error = IoError;
+
+    fn try_from(s: &str) -> IoResult<Self> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::sandbox;
+    use crate::*;
+
+    #[test]
+    fn test_content() {
+        let sandbox = sandbox();
+        let home = sandbox.home().join("content");
+        set_home_override(Some(home.clone()));
+        let config = HomeConfig::with_config_dir("test", "file");
+        set_home_override(Some(sandbox.home().to_path_buf()));
+        assert_eq!(config.path(), &home.join(".config/test/file"));
+
+        // Save
+        config.save("123").unwrap();
+        // Read
+        assert_eq!(config.read_to_string().unwrap(), "123");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_display_path() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "display");
+        assert_eq!(config.display_path(), "~/.config/test/display");
+
+        let config = HomeConfig::with_file("/etc/display");
+        assert_eq!(config.display_path(), "/etc/display");
+    }
+
+    #[test]
+    fn test_name_suffix_prefix() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "config.toml");
+        let dir = config.path().parent().unwrap();
+
+        let lock = config.with_name_suffix(".lock");
+        assert_eq!(lock.path(), &dir.join("config.lock.toml"));
+
+        let backup = config.with_name_prefix("backup.");
+        assert_eq!(backup.path(), &dir.join("backup.config.toml"));
+
+        let config = HomeConfig::with_config_dir("test", "config");
+        assert_eq!(
+            config.with_name_suffix("-old").path(),
+            &dir.join("config-old")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_truncate_and_rewrite() {
+        use std::os::unix::fs::MetadataExt;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "truncate");
+        config.truncate_and_rewrite("123456").unwrap();
+        let inode = config.path().metadata().unwrap().ino();
+
+        config.truncate_and_rewrite("abc").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "abc");
+        assert_eq!(config.path().metadata().unwrap().ino(), inode);
+    }
+
+    #[test]
+    #[cfg(feature = "tempfile")]
+    fn test_stage() {
+        let _sandbox = sandbox();
+        let a = HomeConfig::with_config_dir("test", "stage_a");
+        let b = HomeConfig::with_config_dir("test", "stage_b");
+        a.save("old").unwrap();
+        let _ = b.delete();
+
+        let staged_a = a.stage("new a").unwrap();
+        let staged_b = b.stage("new b").unwrap();
+        assert_eq!(staged_a.path().parent(), a.path().parent());
+        assert_eq!(a.read_to_string().unwrap(), "old");
+        assert!(b.read_to_string_opt().unwrap().is_none());
+
+        staged_a.persist(a.path()).unwrap();
+        staged_b.persist(b.path()).unwrap();
+        assert_eq!(a.read_to_string().unwrap(), "new a");
+        assert_eq!(b.read_to_string().unwrap(), "new b");
+
+        // Dropped without persisting
+        let staged = a.stage("unused").unwrap();
+        let path = staged.path().to_path_buf();
+        drop(staged);
+        assert!(!path.exists());
+        assert_eq!(a.read_to_string().unwrap(), "new a");
+    }
+
+    #[test]
+    fn test_expand_env_cow() {
+        use std::borrow::Cow;
+
+        std::env::set_var("HOME_CONFIG_TEST_VAR", "value");
+        assert!(matches!(expand_env_cow("plain"), Cow::Borrowed("plain")));
+        assert!(matches!(
+            expand_env_cow("$HOME_CONFIG_TEST_UNSET ${} $"),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            expand_env_cow("a/$HOME_CONFIG_TEST_VAR/${HOME_CONFIG_TEST_VAR}b"),
+            "a/value/valueb"
+        );
+        assert_eq!(
+            expand_env_cow("$$HOME_CONFIG_TEST_VAR"),
+            "$HOME_CONFIG_TEST_VAR"
+        );
+        assert_eq!(
+            expand_env_cow("${HOME_CONFIG_TEST_VAR"),
+            "${HOME_CONFIG_TEST_VAR"
+        );
+    }
+
+    #[test]
+    fn test_max_size() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "max_size");
+        config.save(vec![b' '; 4096]).unwrap();
+        assert_eq!(config.read_to_vec().unwrap().len(), 4096);
+
+        let limited = config.clone().with_max_size(1024);
+        let err = limited.read_to_string().unwrap_err();
+        let too_large = TooLargeError::of(&err).unwrap();
+        assert_eq!((too_large.size(), too_large.limit()), (4096, 1024));
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(PathError::of(&err).is_some());
+        assert!(err
+            .to_string()
+            .ends_with("larger than the limit of 1024 bytes: 4096 bytes"));
+
+        // Parsing and derived configs honor it too
+        let err = Error::from(limited.with_name_suffix("").read_to_vec().unwrap_err());
+        assert_eq!(err.kind(), ConfigErrorKind::TooLarge);
+        assert!(limited.with_max_size(4096).read_to_vec().is_ok());
+
+        // A file growing after the size check is cut off at the limit
+        let f = File::open(config.path()).unwrap();
+        let mut buf = Vec::new();
+        let err = config
+            .clone()
+            .with_max_size(10)
+            .read_all(f, &mut buf)
+            .unwrap_err();
+        assert!(TooLargeError::of(&err).is_some());
+        assert_eq!(buf.len(), 11);
+    }
+
+    #[test]
+    fn test_read_lossy() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "lossy");
+        config.save("abc").unwrap();
+        assert_eq!(
+            config.read_to_string_lossy().unwrap(),
+            ("abc".to_string(), false)
+        );
+
+        config.save(b"a\xffb\xc3").unwrap();
+        assert!(config.read_to_string().is_err());
+        assert_eq!(
+            config.read_to_string_lossy().unwrap(),
+            ("a\u{fffd}b\u{fffd}".to_string(), true)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml", feature = "toml"))]
+    fn test_parse_lossy() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "parse_lossy");
+        config
+            .save(b"# caf\xe9\nname = \"123\"\nage = 10\n")
+            .unwrap();
+        assert!(config.toml::<People>().is_err());
+        let (people, replaced) = config.parse_lossy::<People>(Format::Toml).unwrap();
+        assert_eq!(people.name, "123");
+        assert!(replaced);
+
+        config.save(b"# caf\xe9\nname: '123'\nage: 10\n").unwrap();
+        assert!(config.yaml::<People>().is_err());
+        let (people, replaced) = config.parse_lossy::<People>(Format::Yaml).unwrap();
+        assert_eq!(people.age, 10);
+        assert!(replaced);
+
+        config.save("name: '123'\nage: 10\n").unwrap();
+        let (_, replaced) = config.parse_lossy::<People>(Format::Yaml).unwrap();
+        assert!(!replaced);
+        config.save("\n").unwrap();
+        assert!(config
+            .parse_lossy::<People>(Format::Yaml)
+            .unwrap_err()
+            .is_empty_file());
+    }
+
+    #[test]
+    fn test_opt() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "opt");
+        config.save("123").unwrap();
+        assert_eq!(config.read_to_string_opt().unwrap().unwrap(), "123");
+        assert_eq!(config.read_to_vec_opt().unwrap().unwrap(), b"123");
+
+        config.delete().unwrap();
+        assert!(config.read_to_string_opt().unwrap().is_none());
+        let config = HomeConfig::with_config_dir("test-missing-dir", "opt");
+        assert!(config.read_to_vec_opt().unwrap().is_none());
+
+        // Other errors, eg. reading a directory, are kept
+        let dir = HomeConfig::with_config_dir("test", "");
+        assert!(dir.read_to_string_opt().is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+    fn test_serde_opt() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "serde_opt");
+        let _ = config.delete();
+        assert!(config.json_opt::<People>().unwrap().is_none());
+        assert!(config.yaml_opt::<People>().unwrap().is_none());
+        assert!(config.toml_opt::<People>().unwrap().is_none());
+        assert!(config.hcl_opt::<People>().unwrap().is_none());
+
+        let people = People::default();
+        config.save_json(&people).unwrap();
+        assert_eq!(config.json_opt::<People>().unwrap(), Some(people));
+        config.save("{").unwrap();
+        assert!(config.json_opt::<People>().unwrap_err().is_parse_error());
+    }
+
+    #[test]
+    fn test_cwd_file() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_cwd_file(".apprc").unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(config.path(), &cwd.join(".apprc"));
+    }
+
+    #[test]
+    fn test_config_dir() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "config_dir");
+        config.save("").unwrap();
+        assert!(!config.is_config_dir());
+
+        let dir = config.config_dir().unwrap();
+        assert_eq!(dir.path(), &home_dir().join(".config/test"));
+        assert!(dir.is_config_dir());
+        assert!(HomeConfig::with_file("/").config_dir().is_none());
+    }
+
+    #[test]
+    fn test_path_hash() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "path_hash");
+        config.save("").unwrap();
+        let relative = HomeConfig::with_file(".config/test/../test/path_hash");
+        assert_ne!(config.path(), relative.path());
+        assert_eq!(
+            config.canonical_path().unwrap(),
+            relative.canonical_path().unwrap()
+        );
+        assert_eq!(config.path_hash(), relative.path_hash());
+
+        let other = HomeConfig::with_config_dir("test", "path_hash_other");
+        assert_ne!(config.path_hash(), other.path_hash());
+        assert_eq!(
+            other.canonical_path().unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_eq_and_same_file() {
+        use std::collections::HashSet;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "same_file");
+        config.save("").unwrap();
+        let relative = HomeConfig::with_file(".config/test/../test/same_file");
+        let retry = RetryPolicy::new(1, Duration::ZERO, Duration::ZERO);
+
+        assert_eq!(config, config.clone().with_retry(retry));
+        assert_ne!(config, relative);
+        assert!(config.same_file(&relative).unwrap());
+
+        let set = [config.clone(), config.clone(), relative.clone()]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+
+        let other = HomeConfig::with_config_dir("test", "same_file_other");
+        let mut sorted = vec![other.clone(), config.clone()];
+        sorted.sort();
+        assert_eq!(sorted, [config.clone(), other.clone()]);
+        assert!(config.same_file(&other).is_err());
+    }
+
+    #[test]
+    fn test_available_disk_space() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test-missing-dir", "config");
+        assert!(config.available_disk_space().unwrap() > 0);
+        assert!(
+            HomeConfig::with_file("config")
+                .available_disk_space()
+                .unwrap()
+                > 0
+        );
+
+        config.assert_space_for(1).unwrap();
+        let err = config.assert_space_for(u64::MAX).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::StorageFull);
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "json"))]
+    fn test_serde_home_config() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct State {
+            managed: Vec<HomeConfig>,
+        }
+
+        let _sandbox = sandbox();
+        let state = State {
+            managed: vec![
+                HomeConfig::with_config_dir("test", "config.json"),
+                HomeConfig::with_file("test.toml"),
+            ],
+        };
+        let json = serde_json::to_value(&state).unwrap();
+        assert_eq!(
+            json["managed"][0],
+            serde_json::json!(home_dir().join(".config/test/config.json"))
+        );
+        assert_eq!(serde_json::from_value::<State>(json).unwrap(), state);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let config = HomeConfig::with_file(OsStr::from_bytes(b"\xFF.json"));
+            assert!(serde_json::to_string(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_from_manifest_dir() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::from_manifest_dir("Cargo.toml").unwrap();
+        assert_eq!(
+            config.path(),
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml")
+        );
+        assert!(config
+            .read_to_string()
+            .unwrap()
+            .contains("name = \"home-config\""));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_with_runtime_dir() {
+        let _sandbox = sandbox();
+        let original = std::env::var_os("XDG_RUNTIME_DIR");
+
+        std::env::set_var("XDG_RUNTIME_DIR", "/run/user/1000");
+        let config = HomeConfig::with_runtime_dir("app", "app.sock").unwrap();
+        assert_eq!(config.path(), Path::new("/run/user/1000/app/app.sock"));
+        let err = HomeConfig::with_runtime_dir("../app", "app.sock").unwrap_err();
+        assert_eq!(IoError::from(err).kind(), ErrorKind::InvalidInput);
+
+        for value in [None, Some("relative")] {
+            match value {
+                Some(value) => std::env::set_var("XDG_RUNTIME_DIR", value),
+                None => std::env::remove_var("XDG_RUNTIME_DIR"),
+            }
+            let err = HomeConfig::with_runtime_dir("app", "app.sock").unwrap_err();
+            assert!(matches!(err, RuntimeDirError::NotSet));
+            assert_eq!(IoError::from(err).kind(), ErrorKind::NotFound);
+        }
+
+        match original {
+            Some(original) => std::env::set_var("XDG_RUNTIME_DIR", original),
+            None => std::env::remove_var("XDG_RUNTIME_DIR"),
+        }
+    }
+
+    #[test]
+    fn test_try_with_config_dir() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::try_with_config_dir("test", "config").unwrap();
+        assert_eq!(
+            config.path(),
+            HomeConfig::with_config_dir("test", "config").path()
+        );
+
+        for name in [
+            "", "a/b", "../etc", "..", ".", "a\\b", "a\0b", "a\nb", "a:b",
+        ] {
+            let err = HomeConfig::try_with_config_dir(name, "config").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{:?}", name);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "app name is empty")]
+    fn test_with_config_dir_empty() {
+        let _sandbox = sandbox();
+        HomeConfig::with_config_dir("", "config");
+    }
+
+    #[test]
+    fn test_read_cached() {
+        use std::time::{Duration, SystemTime};
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "cached");
+        config.save("123").unwrap();
+
+        let mut cache = ConfigCache::new();
+        assert_eq!(
+            config.read_to_string_cached(&mut cache).unwrap().unwrap(),
+            "123"
+        );
+        assert_eq!(config.read_to_string_cached(&mut cache).unwrap(), None);
+        assert_eq!(cache.content(), "123");
+
+        config.save("456").unwrap();
+        File::options()
+            .write(true)
+            .open(config.path())
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(
+            config.read_to_string_cached(&mut cache).unwrap().unwrap(),
+            "456"
+        );
+        assert_eq!(config.read_to_string_cached(&mut cache).unwrap(), None);
+
+        // Saves keeping the modification time are still seen
+        let modified = fs::metadata(config.path()).unwrap().modified().unwrap();
+        let set_modified = || {
+            File::options()
+                .write(true)
+                .open(config.path())
+                .unwrap()
+                .set_modified(modified)
+                .unwrap()
+        };
+        config.save("7890").unwrap();
+        set_modified();
+        assert_eq!(
+            config.read_to_string_cached(&mut cache).unwrap().unwrap(),
+            "7890"
+        );
+        let tmp = config.with_name_suffix(".tmp");
+        tmp.save("abcd").unwrap();
+        fs::rename(tmp.path(), config.path()).unwrap();
+        set_modified();
+        #[cfg(unix)]
+        assert_eq!(
+            config.read_to_string_cached(&mut cache).unwrap().unwrap(),
+            "abcd"
+        );
+        assert_eq!(config.read_to_string_cached(&mut cache).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        use std::time::Duration;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "timeout");
+        config.save("123").unwrap();
+        assert_eq!(
+            config
+                .read_to_string_timeout(Duration::from_secs(5))
+                .unwrap(),
+            "123"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_path_conversions() {
+        // Verbatim
+        let config = HomeConfig::from(PathBuf::from(".apprc"));
+        assert_eq!(config.path(), Path::new(".apprc"));
+        assert_ne!(config, HomeConfig::with_file(".apprc"));
+        assert_eq!(HomeConfig::from(Path::new(".apprc")), config);
+        assert_eq!(config.into_path(), PathBuf::from(".apprc"));
+
+        // Home joined
+        let home = home_dir();
+        for (s, path) in [
+            ("~", home.clone()),
+            ("~/", home.clone()),
+            ("~/.apprc", home.join(".apprc")),
+            ("/etc/apprc", PathBuf::from("/etc/apprc")),
+        ] {
+            assert_eq!(s.parse::<HomeConfig>().unwrap().path(), &path);
+            assert_eq!(HomeConfig::try_from(s).unwrap().path(), &path);
+        }
+        assert_eq!(
+            HomeConfig::try_from("~/.apprc").unwrap(),
+            HomeConfig::with_file(".apprc")
+        );
+
+        for s in ["", ".apprc", "~user/.apprc"] {
+            let err = s.parse::<HomeConfig>().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let config = HomeConfig::with_config_dir("test", "display");
+        assert_eq!(config.to_string(), config.path().display().to_string());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_os_str() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "os_str");
+        config.save("123").unwrap();
+
+        let output = std::process::Command::new("cat")
+            .arg(&config)
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"123");
+
+        let path = config.path().clone();
+        assert_eq!(OsString::from(config), path.into_os_string());
+    }
+
+    #[test]
+    fn test_open() {
+        use std::io::{Seek, SeekFrom};
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "open");
+        config.open_write().unwrap().write_all(b"123456").unwrap();
+
+        let mut f = config.open_read().unwrap();
+        f.seek(SeekFrom::Start(3)).unwrap();
+        let mut buf = String::new();
+        f.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "456");
+
+        config.open_write().unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "");
+    }
+
+    #[test]
+    fn test_retry() {
+        use std::io::{Error, ErrorKind, Write};
+        use std::time::{Duration, Instant};
+
+        struct FlakyWriter {
+            failures: u32,
+            buf: Vec<u8>,
+        }
+
+        impl Write for FlakyWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.failures > 0 {
+                    self.failures -= 1;
+                    return Err(Error::from(ErrorKind::WouldBlock));
+                }
+                self.buf.write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let _sandbox = sandbox();
+        let policy = RetryPolicy::new(3, Duration::from_millis(20), Duration::from_millis(100));
+
+        // Transient errors: 20ms + 40ms of backoff
+        let mut writer = FlakyWriter {
+            failures: 2,
+            buf: Vec::new(),
+        };
+        let now = Instant::now();
+        policy.run(|| writer.write_all(b"123")).unwrap();
+        let elapsed = now.elapsed();
+        assert_eq!(writer.buf, b"123");
+        assert!(elapsed >= Duration::from_millis(60));
+        assert!(elapsed < Duration::from_secs(1));
+
+        // Out of attempts
+        let mut writer = FlakyWriter {
+            failures: 5,
+            buf: Vec::new(),
+        };
+        let err = policy.run(|| writer.write_all(b"123")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        let retry = err.get_ref().unwrap().downcast_ref::<RetryError>().unwrap();
+        assert_eq!(retry.attempts(), 3);
+
+        // Never retried
+        let mut count = 0;
+        let err = policy
+            .run(|| {
+                count += 1;
+                Err::<(), _>(Error::from(ErrorKind::InvalidData))
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(count, 1);
+
+        let config = HomeConfig::with_config_dir("test", "retry").with_retry(policy);
+        config.save("123").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "123");
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let outer = sandbox();
+        let config = HomeConfig::with_config_dir("test", "sandbox");
+        assert!(config.path().starts_with(outer.home()));
+        config.save("outer").unwrap();
+
+        // Other threads have their own
+        let (home, path) = thread::spawn(|| {
+            let sandbox = sandbox();
+            let config = HomeConfig::with_config_dir("test", "sandbox");
+            config.save("inner").unwrap();
+            (sandbox.home().to_path_buf(), config.path().clone())
+        })
+        .join()
+        .unwrap();
+        assert_ne!(home, outer.home());
+        assert!(!path.exists());
+        assert_eq!(config.read_to_string().unwrap(), "outer");
+
+        // Nested
+        let inner = sandbox();
+        assert!(HomeConfig::with_file("a").path().starts_with(inner.home()));
+        drop(inner);
+        assert!(HomeConfig::with_file("a").path().starts_with(outer.home()));
+
+        let home = outer.home().to_path_buf();
+        drop(outer);
+        assert!(!home.exists());
+
+        // Existing directories with the next names are skipped and kept
+        let next = crate::test_support::NEXT.load(std::sync::atomic::Ordering::Relaxed);
+        let taken = (next..next + 64)
+            .map(|n| {
+                let name = format!("home-config-sandbox-{}-{}", std::process::id(), n);
+                std::env::temp_dir().join(name)
+            })
+            .filter(|dir| fs::create_dir(dir).is_ok())
+            .collect::<Vec<_>>();
+        for dir in &taken {
+            fs::write(dir.join("keep"), "").unwrap();
+        }
+        let sandbox = sandbox();
+        assert!(!taken.iter().any(|dir| dir == sandbox.home()));
+        assert_eq!(fs::read_dir(sandbox.home()).unwrap().count(), 0);
+        drop(sandbox);
+        for dir in &taken {
+            assert!(dir.join("keep").exists());
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_home_cache() {
+        let _sandbox = sandbox();
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(cached_home_dir(), home);
+        assert_eq!(cached_home_dir(), home);
+        reset_home_cache();
+        assert_eq!(cached_home_dir(), home);
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "toml"))]
+    fn test_error_display_and_source() {
+        use std::error::Error;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "error.json");
+        config.delete().unwrap();
+        let err = config.json::<People>().unwrap_err();
+        assert!(err.to_string().starts_with("io error: "));
+        let source = err.source().unwrap().downcast_ref::<IoError>().unwrap();
+        assert_eq!(source.kind(), ErrorKind::NotFound);
+
+        config.save("{").unwrap();
+        let err = config.json::<People>().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with(&format!("json error: {}: EOF", config.display_path())));
+        assert!(err.source().unwrap().is::<serde_json::Error>());
+
+        let toml = HomeConfig::with_config_dir("test", "error.toml");
+        toml.save("name = ").unwrap();
+        let err = toml.toml::<People>().unwrap_err();
+        assert!(err.to_string().starts_with("toml parse error: "));
+        assert!(err.source().unwrap().is::<toml::de::Error>());
+
+        // Boxed as `dyn Error`
+        let boxed: Box<dyn Error + Send + Sync> = config.json::<People>().unwrap_err().into();
+        assert!(boxed.source().is_some());
+    }
+
+    #[test]
+    fn test_path_error() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "path_error");
+        config.delete().unwrap();
+
+        let err = config.read_to_string().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(PathError::of(&err).unwrap().path(), config.path());
+        assert!(err
+            .to_string()
+            .starts_with(&format!("{}: ", config.display_path())));
+
+        // Wrapped once
+        let err = config.read_to_vec().unwrap_err();
+        assert!(PathError::of(&err).is_some());
+        assert_eq!(err.to_string().matches(&config.display_path()).count(), 1);
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+    fn test_parse_error_path() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "broken");
+        let prefix = format!("{}: ", config.display_path());
+        config.save("name = [\n  \"a\",\n").unwrap();
+
+        let err = config.json::<People>().unwrap_err();
+        assert_eq!(err.path(), Some(config.path().as_path()));
+        assert!(err.to_string().contains(&prefix));
+        let err = config.yaml::<People>().unwrap_err();
+        assert_eq!(err.path(), Some(config.path().as_path()));
+        assert!(err.to_string().contains(&prefix));
+        let err = config.toml::<People>().unwrap_err();
+        assert_eq!(err.path(), Some(config.path().as_path()));
+        assert!(err.to_string().contains(&prefix));
+        let err = config.hcl::<People>().unwrap_err();
+        assert_eq!(err.path(), Some(config.path().as_path()));
+        assert!(err.to_string().contains(&prefix));
+
+        // Kept by the unified error, and not repeated
+        let err = Error::from(config.json::<People>().unwrap_err());
+        assert_eq!(err.path(), Some(config.path().as_path()));
+        assert_eq!(err.to_string().matches(&config.display_path()).count(), 1);
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "toml"))]
+    fn test_unified_error() {
+        fn load(json: &HomeConfig, toml: &HomeConfig) -> Result<(People, People), Error> {
+            Ok((json.json()?, toml.toml()?))
+        }
+
+        let _sandbox = sandbox();
+        let json = HomeConfig::with_config_dir("test", "unified.json");
+        let toml = HomeConfig::with_config_dir("test", "unified.toml");
+        json.save(r#"{"name": "123", "age": 18}"#).unwrap();
+        toml.save("name = ").unwrap();
+        let err = load(&json, &toml).unwrap_err();
+        assert!(matches!(err, Error::TomlDe(_)));
+        assert!(err.to_string().starts_with("toml parse error: "));
+
+        json.delete().unwrap();
+        assert!(matches!(load(&json, &toml), Err(Error::Io(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_with_comments() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "comments.json");
+        config
+            .save(
+                r#"{
+    // The name
+    "name": "https://example.com/a//b", /* inline */
+    /* multi
+       line */
+    "age": 18 // trailing
+}"#,
+            )
+            .unwrap();
+        let people = config.json_with_comments::<People>().unwrap();
+        assert_eq!(people.name, "https://example.com/a//b");
+        assert_eq!(people.age, 18);
+
+        // Escaped quotes do not end the string
+        config.save(r#"{"name": "a\" // b", "age": 1}"#).unwrap();
+        let people = config.json_with_comments::<People>().unwrap();
+        assert_eq!(people.name, r#"a" // b"#);
+
+        // Positions are kept
+        config.save("// comment\n{\"age\": }").unwrap();
+        let err = config.json_with_comments::<People>().unwrap_err();
+        assert!(matches!(&err, JsonError::Serde(e) if e.line() == 2 && e.column() == 9));
+        // Only recorded with the `field-path` feature
+        config.save(r#"{"name": 1, "age": 1}"#).unwrap();
+        let err = config.json_with_comments::<People>().unwrap_err();
+        assert_eq!(err.field_path().is_some(), cfg!(feature = "field-path"));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_kv_store() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "kv_store.json");
+        config.delete().unwrap();
+
+        let mut store = config.kv_store().unwrap();
+        assert_eq!(store.get::<u32>("age"), None);
+        store.set("name", "123").unwrap();
+        store.set("age", 18).unwrap();
+        assert_eq!(store.get::<u32>("age"), Some(18));
+        assert_eq!(store.get::<u32>("name"), None);
+        assert_eq!(store.keys().collect::<Vec<_>>(), ["age", "name"]);
+
+        // Persisted
+        let mut store = config.kv_store().unwrap();
+        assert_eq!(store.get::<String>("name").unwrap(), "123");
+        assert!(store.remove("name").unwrap());
+        assert!(!store.remove("name").unwrap());
+        assert!(!config.kv_store().unwrap().contains_key("name"));
+        assert!(config.kv_store().unwrap().contains_key("age"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+    fn test_empty_file() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "empty");
+        for content in ["", " \n\t\r\n", "\u{feff}", "\u{feff}\n"] {
+            config.save(content).unwrap();
+            assert!(matches!(config.json::<People>(), Err(JsonError::Empty(_))));
+            assert!(matches!(config.yaml::<People>(), Err(YamlError::Empty(_))));
+            assert!(matches!(
+                config.toml::<People>(),
+                Err(TomlParseError::Empty(_))
+            ));
+            assert!(matches!(config.hcl::<People>(), Err(HclError::Empty(_))));
+
+            let err = Error::from(config.yaml::<Option<People>>().unwrap_err());
+            assert_eq!(err.kind(), ConfigErrorKind::Empty);
+            assert!(err.is_empty_file());
+            assert!(!err.is_parse_error());
+            assert_eq!(
+                err.to_string(),
+                format!("{}: config file is empty", config.display_path())
+            );
+            assert_eq!(err.path(), Some(config.path().as_path()));
+        }
+
+        // Treated like a missing file where that is not an error
+        let store = config.kv_store().unwrap();
+        assert_eq!(store.keys().count(), 0);
+        config.set_json_defaults(&People::default()).unwrap();
+        assert_eq!(config.json::<People>().unwrap(), People::default());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_save_json_streaming() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Large {
+            items: Vec<People>,
+        }
+
+        let _sandbox = sandbox();
+        let large = Large {
+            items: (0..10_000)
+                .map(|age| People {
+                    name: age.to_string(),
+                    age,
+                })
+                .collect(),
+        };
+        let config = HomeConfig::with_config_dir("test", "streaming.json");
+        config.save_json_streaming(&large).unwrap();
+        assert_eq!(config.json::<Large>().unwrap(), large);
+        assert_eq!(
+            config.read_to_string().unwrap(),
+            HomeConfig::to_json_string(&large).unwrap()
+        );
+
+        // Nothing is left behind, even on errors
+        let mut map = std::collections::HashMap::new();
+        map.insert(vec![1], 1);
+        assert!(config.save_json_streaming(&map).is_err());
+        assert_eq!(config.json::<Large>().unwrap(), large);
+        let dir = fs::read_dir(config.path().parent().unwrap()).unwrap();
+        assert!(!dir
+            .map(|entry| entry.unwrap().file_name())
+            .any(|name| name.to_string_lossy().starts_with(".streaming.json")));
+
+        // Concurrent saves each use their own temporary file
+        let threads = (0..8)
+            .map(|age| {
+                let config = config.clone();
+                thread::spawn(move || {
+                    let people = People {
+                        name: "XiaoMing".into(),
+                        age,
+                    };
+                    config.save_json_streaming(&people).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        config.json::<People>().unwrap();
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "json"))]
+    fn test_save_json_streaming_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "streaming-secret.json");
+        config.save_secret("{}").unwrap();
+        config
+            .save_json_streaming(serde_json::json!({"token": "123"}))
+            .unwrap();
+        let mode = config.path().metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_save_json_with_schema() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "schema.json");
+        let url = "https://example.com/schema.json";
+        let people = People {
+            name: "123".to_string(),
+            age: 1,
+        };
+        config.save_json_with_schema(&people, url).unwrap();
+        assert_eq!(
+            config.read_to_string().unwrap(),
+            "{\n  \"$schema\": \"https://example.com/schema.json\",\n  \"name\": \"123\",\n  \"age\": 1\n}"
+        );
+        assert_eq!(config.json::<People>().unwrap(), people);
+
+        // Nested values are written as by `save_json`
+        #[derive(Serialize)]
+        struct Nested {
+            people: People,
+            list: Vec<People>,
+        }
+        let nested = Nested {
+            people: people.clone(),
+            list: vec![people.clone()],
+        };
+        config.save_json_with_schema(&nested, url).unwrap();
+        let s = HomeConfig::to_json_string(&nested).unwrap();
+        let s = s.replacen("{\n", &format!("{{\n  \"$schema\": \"{}\",\n", url), 1);
+        assert_eq!(config.read_to_string().unwrap(), s);
+
+        let mut map = serde_json::Map::new();
+        map.insert("$schema".to_string(), "old".into());
+        config.save_json_with_schema(&map, url).unwrap();
+        let value = config.json::<serde_json::Value>().unwrap();
+        assert_eq!(value, serde_json::json!({ "$schema": url }));
+        assert!(config.save_json_with_schema(1, url).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_max_size() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "max_size.json");
+        config.save_json(vec![0; 100]).unwrap();
+        let err = config
+            .clone()
+            .with_max_size(10)
+            .json::<Vec<u8>>()
+            .unwrap_err();
+        assert_eq!(err.kind(), ConfigErrorKind::TooLarge);
+
+        // Streaming parses stop reading after the limit, also when the file grows
+        let limited = config.clone().with_max_size(10);
+        let seed = std::marker::PhantomData::<Vec<u8>>;
+        let err = limited.json_seeded(seed).unwrap_err();
+        assert_eq!(err.kind(), ConfigErrorKind::TooLarge);
+
+        config.save("[").unwrap();
+        let mut items = limited.json_array_reader::<u8>().unwrap();
+        config.save_json(vec![0; 100]).unwrap();
+        let err = items.find_map(Result::err).unwrap();
+        assert_eq!(err.kind(), ConfigErrorKind::TooLarge);
+
+        config.save("1\n").unwrap();
+        let mut lines = limited.json_lines_iter::<u8>().unwrap();
+        config.save("1\n2\n3\n4\n5\n6\n7\n8\n").unwrap();
+        let err = lines.find_map(Result::err).unwrap();
+        assert_eq!(err.kind(), ConfigErrorKind::TooLarge);
+        assert_eq!(config.json_lines::<u8>().unwrap().len(), 8);
+    }
+
+    #[test]
+    #[cfg(feature = "lenient")]
+    fn test_json_lenient() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Server {
+            host: String,
+            port: u16,
+            timeout: Option<u32>,
+        }
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "lenient.json");
+        config
+            .save(r#"{"hostname": "a", "port": "8080", "timeuot": 5}"#)
+            .unwrap();
+        let (server, warnings) = config
+            .json_lenient_with_aliases::<Server>(&[("hostname", "host")])
+            .unwrap();
+        assert_eq!(
+            server,
+            Server {
+                host: "a".to_string(),
+                port: 8080,
+                timeout: None,
+            }
+        );
+        let warnings = warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                "hostname: deprecated, renamed to `host`",
+                r#"port: converted string "8080" to a number"#,
+                "timeuot: unknown key, ignored",
+            ]
+        );
+
+        config.save(r#"{"host": "a", "port": "http"}"#).unwrap();
+        let err = config.json_lenient::<Server>().unwrap_err();
+        assert!(err.is_parse_error());
+
+        // Out of range after the conversion, the original error is kept
+        config.save(r#"{"host": "a", "port": "70000"}"#).unwrap();
+        let err = config.json_lenient::<Server>().unwrap_err();
+        assert_eq!(err.field_path(), Some("port"));
+        assert!(err.to_string().contains(r#"invalid type: string "70000""#));
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_load_all_profiles() {
+        let _sandbox = sandbox();
+        let dir = HomeConfig::with_config_dir("test-profiles", "");
+        let _ = fs::remove_dir_all(dir.path());
+        let profiles = HomeConfig::load_all_profiles::<People>("test-profiles", Format::Toml);
+        assert!(profiles.unwrap().is_empty());
+
+        let work = People {
+            name: "work".to_string(),
+            age: 1,
+        };
+        let home = People {
+            name: "home".to_string(),
+            age: 2,
+        };
+        HomeConfig::with_config_dir("test-profiles", "work.toml")
+            .save_toml(&work)
+            .unwrap();
+        HomeConfig::with_config_dir("test-profiles", "home.toml")
+            .save_toml(&home)
+            .unwrap();
+        HomeConfig::with_config_dir("test-profiles", "broken.toml")
+            .save("name = ")
+            .unwrap();
+        HomeConfig::with_config_dir("test-profiles", "other.json")
+            .save("{}")
+            .unwrap();
+
+        let profiles = HomeConfig::load_all_profiles::<People>("test-profiles", Format::Toml);
+        let mut profiles = profiles.unwrap().into_iter().collect::<Vec<_>>();
+        profiles.sort_by_key(|(_, people)| people.age);
+        assert_eq!(
+            profiles,
+            [("work".to_string(), work), ("home".to_string(), home)]
+        );
+        assert!(HomeConfig::load_all_profiles::<People>("../test", Format::Toml).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "yaml", feature = "toml"))]
+    fn test_as_value() {
+        let _sandbox = sandbox();
+        let toml = HomeConfig::with_config_dir("test", "as_value.toml");
+        let yaml = HomeConfig::with_config_dir("test", "as_value.yaml");
+        toml.save("name = \"123\"\nage = 18\n[db]\nports = [1, 2]\n")
+            .unwrap();
+        yaml.save("name: '123'\nage: 18\ndb:\n  ports: [1, 2]\n")
+            .unwrap();
+
+        let expected = serde_json::json!({"name": "123", "age": 18, "db": {"ports": [1, 2]}});
+        assert_eq!(toml.as_value(Format::Toml).unwrap(), expected);
+        assert_eq!(yaml.as_value(Format::Yaml).unwrap(), expected);
+        assert!(matches!(toml.as_value(Format::Json), Err(Error::Json(_))));
+    }
+
+    #[test]
+    fn test_errors_send_sync() {
+        fn assert_send_sync<T: std::error::Error + Send + Sync + 'static>() {}
+        assert_send_sync::<Error>();
+        assert_send_sync::<PathError>();
+        assert_send_sync::<RetryError>();
+        #[cfg(feature = "json")]
+        assert_send_sync::<JsonError>();
+        #[cfg(feature = "json")]
+        assert_send_sync::<ConfigFileError>();
+        #[cfg(feature = "yaml")]
+        assert_send_sync::<YamlError>();
+        #[cfg(feature = "toml")]
+        assert_send_sync::<TomlParseError>();
+        #[cfg(feature = "toml")]
+        assert_send_sync::<TomlSaveError>();
+        #[cfg(feature = "hcl")]
+        assert_send_sync::<HclError>();
+        #[cfg(feature = "watch")]
+        assert_send_sync::<WatchError>();
+        #[cfg(all(feature = "watch", feature = "json"))]
+        assert_send_sync::<ReloadError<JsonError>>();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_error_kind() {
+        let not_found = JsonError::Io(IoError::from(ErrorKind::NotFound));
+        assert_eq!(not_found.kind(), ConfigErrorKind::NotFound);
+        assert!(not_found.is_not_found());
+        assert!(!not_found.is_parse_error());
+
+        let denied = JsonError::Io(IoError::from(ErrorKind::PermissionDenied));
+        assert!(denied.is_permission_denied());
+        assert!(!denied.is_not_found());
+        let other = Error::from(IoError::from(ErrorKind::Interrupted));
+        assert_eq!(other.kind(), ConfigErrorKind::Io);
+
+        let syntax = JsonError::from(serde_json::from_str::<People>("{").unwrap_err());
+        assert_eq!(syntax.kind(), ConfigErrorKind::Syntax);
+        assert!(syntax.is_parse_error());
+        let data = Error::from(serde_json::from_str::<People>("{}").unwrap_err());
+        assert_eq!(data.kind(), ConfigErrorKind::Data);
+        assert!(data.is_parse_error());
+
+        let conflict = ConfigFileError::Conflict {
+            theirs_changed_at: None,
+        };
+        assert!(conflict.is_conflict());
+        assert!(!conflict.is_parse_error());
+        assert!(ConfigFileError::from(not_found).is_not_found());
+
+        #[cfg(feature = "toml")]
+        {
+            let err = HomeConfig::from_toml_str::<People>("name = ").unwrap_err();
+            assert!(TomlParseError::from(err).is_parse_error());
+            let err = HomeConfig::to_toml_string(&()).unwrap_err();
+            assert_eq!(Error::from(err).kind(), ConfigErrorKind::Serialize);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "toml"))]
+    fn test_semantic_eq() {
+        let _sandbox = sandbox();
+        let a = HomeConfig::with_config_dir("test", "semantic_a.toml");
+        let b = HomeConfig::with_config_dir("test", "semantic_b.toml");
+        a.save("name = \"123\"\nage = 18\n").unwrap();
+        b.save("# comment\nage   =   18\nname = '123'\n").unwrap();
+        assert!(a.semantic_eq(&b, Format::Toml).unwrap());
+
+        b.save("name = \"123\"\nage = 19\n").unwrap();
+        assert!(!a.semantic_eq(&b, Format::Toml).unwrap());
+
+        let a = a.with_name_suffix(".json");
+        let b = b.with_name_suffix(".json");
+        a.save(r#"{"name": "123", "tags": [1, 2]}"#).unwrap();
+        b.save(r#"{ "tags": [1,2],  "name":"123" }"#).unwrap();
+        assert!(a.semantic_eq(&b, Format::Json).unwrap());
+        b.save(r#"{"name": "123", "tags": [2, 1]}"#).unwrap();
+        assert!(!a.semantic_eq(&b, Format::Json).unwrap());
+    }
+
+    #[test]
+    #[cfg(all(feature = "diagnostics", feature = "toml"))]
+    fn test_parse_diagnostic() {
+        use miette::{GraphicalReportHandler, GraphicalTheme};
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "diagnostic.toml");
+        config.save("name = \"123\"\nage = \n").unwrap();
+        let diagnostic = config.parse_diagnostic::<People>(Format::Toml).unwrap_err();
+
+        let mut report = String::new();
+        GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+            .with_width(80)
+            .render_report(&mut report, &diagnostic)
+            .unwrap();
+        let expected = r#"
+  × toml parse error: invalid string, expected `"`, `'` at line 2 column 7
+   ╭─[~/.config/test/diagnostic.toml:2:7]
+ 1 │ name = "123"
+ 2 │ age = 
+   ·       ▲
+   ·       ╰── here
+   ╰────
+  help: fix the config file `~/.config/test/diagnostic.toml`
+"#;
+        assert_eq!(report, &expected[1..]);
+        assert!(diagnostic.error().is_parse_error());
+    }
+
+    #[test]
+    #[cfg(all(feature = "diagnostics", feature = "json"))]
+    fn test_parse_diagnostic_multibyte() {
+        use miette::Diagnostic;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "diagnostic.json");
+        let content = "{\n  \"name\": \"小明 🎉\", \"age\": x\n}";
+        config.save(content).unwrap();
+        let diagnostic = config.parse_diagnostic::<People>(Format::Json).unwrap_err();
+        let label = diagnostic.labels().unwrap().next().unwrap();
+        assert_eq!(label.offset(), content.find('x').unwrap());
+    }
+
+    #[test]
+    #[cfg(all(feature = "field-path", feature = "json"))]
+    fn test_field_path() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Servers {
+            servers: Vec<Server>,
+        }
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "field_path");
+        #[cfg(feature = "json")]
+        {
+            config
+                .save(r#"{"servers": [{"host": "a", "port": 1}, {"host": "b", "port": "2"}]}"#)
+                .unwrap();
+            let err = config.json::<Servers>().unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+            assert!(err.to_string().starts_with(&format!(
+                "json error: {}: servers[1].port: invalid type",
+                config.display_path()
+            )));
+            let err = Error::from(err);
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+            assert_eq!(err.location(), Some((1, 64)));
+            let err = config.json::<Servers>().unwrap_err();
+            assert!(err.is_parse_error());
+            // The variant does not depend on the feature
+            assert!(matches!(&err, JsonError::Serde(e) if e.line() == 1));
+
+            // Every way of parsing records it
+            let err = config.json_with_comments::<Servers>().unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+            let reader = config.json_borrowed().unwrap();
+            let err = reader.parse::<Servers>().unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+            let err = config.json_lines::<Servers>().unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+            let err = HomeConfig::layered_json::<Servers>(&config, &config).unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+
+            // Nothing to point at
+            for content in ["[]", "{"] {
+                config.save(content).unwrap();
+                let err = config.json::<Servers>().unwrap_err();
+                assert!(matches!(err, JsonError::Serde(_)));
+                assert_eq!(err.field_path(), None);
+            }
+        }
+        #[cfg(feature = "yaml")]
+        {
+            config
+                .save(
+                    "servers:
+  - host: a
+    port: [1]
+",
+                )
+                .unwrap();
+            let err = config.yaml::<Servers>().unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[0].port"));
+            assert_eq!(Error::from(err).location(), Some((3, 11)));
+        }
+        #[cfg(feature = "toml")]
+        {
+            config
+                .save(
+                    "[[servers]]
+host = \"a\"
+port = 1
+
+[[servers]]
+host = \"b\"
+port = \"2\"
+",
+                )
+                .unwrap();
+            let err = config.toml::<Servers>().unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+            assert_eq!(Error::from(err).location(), Some((7, 8)));
+        }
+        #[cfg(feature = "hcl")]
+        {
+            config
+                .save("servers {\n  host = \"a\"\n  port = 1\n}\nservers {\n  host = \"b\"\n  port = \"x\"\n}\n")
+                .unwrap();
+            let err = config.hcl::<Servers>().unwrap_err();
+            assert_eq!(err.field_path(), Some("servers[1].port"));
+        }
+    }
+
+    #[test]
+    fn test_error_location() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "location");
+        config.delete().unwrap();
+        let err = Error::from(config.read_to_string().unwrap_err());
+        assert_eq!(err.location(), None);
+        assert_eq!(err.span(), None);
+
+        #[cfg(feature = "json")]
+        {
+            config.save("{\n  \"age\": }").unwrap();
+            let err = Error::from(config.json::<People>().unwrap_err());
+            assert_eq!(err.location(), Some((2, 10)));
+        }
+        #[cfg(feature = "yaml")]
+        {
+            config.save("name: 123\nage: [").unwrap();
+            let err = Error::from(config.yaml::<People>().unwrap_err());
+            assert_eq!(err.location(), Some((2, 6)));
+            assert_eq!(err.span(), Some(15..15));
+        }
+        #[cfg(feature = "toml")]
+        {
+            config.save("name = \"123\"\nage = ").unwrap();
+            let err = Error::from(config.toml::<People>().unwrap_err());
+            assert_eq!(err.location(), Some((2, 7)));
+            assert_eq!(err.span(), Some(19..19));
+
+            config.save(b"\xEF\xBB\xBFname = \"\xFF\"").unwrap();
+            assert!(config.toml::<People>().unwrap_err().is_parse_error());
+        }
+        #[cfg(feature = "hcl")]
+        {
+            config.save("name = \"123\"\nage = {").unwrap();
+            let err = Error::from(config.hcl::<People>().unwrap_err());
+            assert_eq!(err.location().unwrap().0, 2);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_with_includes() {
+        use serde_json::json;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test-include", "config.json");
+        let server = HomeConfig::with_config_dir("test-include", "server.json");
+        server
+            .save_json(json!({"host": "localhost", "port": 80}))
+            .unwrap();
+        config
+            .save_json(json!({
+                "name": "app",
+                "server": {"$include": "server.json", "port": 8080},
+            }))
+            .unwrap();
+        assert_eq!(
+            config.json_with_includes::<serde_json::Value>().unwrap(),
+            json!({"name": "app", "server": {"host": "localhost", "port": 8080}})
+        );
+
+        // Cycle
+        server
+            .save_json(json!({"$include": ["config.json"]}))
+            .unwrap();
+        let err = config
+            .json_with_includes::<serde_json::Value>()
+            .unwrap_err();
+        assert!(err.to_string().contains("include cycle"));
+
+        server.save_json(json!({"$include": 1})).unwrap();
+        assert!(config
+            .json_with_includes::<serde_json::Value>()
+            .unwrap_err()
+            .is_parse_error());
+    }
+
+    #[test]
+    #[cfg(feature = "schema")]
+    fn test_validate_schema() {
+        use serde_json::json;
+
+        let _sandbox = sandbox();
+        let schema = json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": {"type": "string"},
+                "port": {"type": "integer", "maximum": 65535},
+            },
+        });
+        let config = HomeConfig::with_config_dir("test", "schema.json");
+        config
+            .save_json(json!({"name": "app", "port": 80}))
+            .unwrap();
+        config.validate_schema(&schema).unwrap();
+
+        config.save_json(json!({"port": 70000})).unwrap();
+        let mut errors = config.validate_schema(&schema).unwrap_err();
+        errors.sort_by(|a, b| a.path().cmp(b.path()));
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].path(), "");
+        assert!(errors[0]
+            .message()
+            .contains("\"name\" is a required property"));
+        assert_eq!(errors[1].path(), "/port");
+        assert!(errors[1].to_string().starts_with("/port: 70000"));
+
+        let errors = config.validate_schema(&json!({"type": 1})).unwrap_err();
+        assert!(errors[0].message().starts_with("invalid schema"));
+
+        config.save("{").unwrap();
+        let errors = config.validate_schema(&schema).unwrap_err();
+        assert!(errors[0].message().starts_with("json error"));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_flat_map() {
+        use serde_json::json;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "flat.json");
+        let data = json!({
+            "name": "app",
+            "server": {
+                "host": "localhost",
+                "tls": { "cert": "a.pem", "enabled": true },
+                "ports": [80, 443],
+            },
+            "empty": {},
+        });
+        config.save_json(&data).unwrap();
+
+        let map = config.json_as_flat_map().unwrap();
+        assert_eq!(
+            map.keys().collect::<Vec<_>>(),
+            [
+                "empty",
+                "name",
+                "server.host",
+                "server.ports",
+                "server.tls.cert",
+                "server.tls.enabled"
+            ]
+        );
+        assert_eq!(map["server.tls.cert"], json!("a.pem"));
+        assert_eq!(map["server.ports"], json!([80, 443]));
+        assert_eq!(HomeConfig::json_unflatten(&map), data);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_diff_from_disk() {
+        use serde_json::json;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "diff.json");
+        let saved = json!({"name": "a", "server": {"host": "x", "port": 80}, "old": 1});
+        config.save_json(&saved).unwrap();
+        assert_eq!(config.json_diff_from_disk(&saved).unwrap(), json!(null));
+
+        let mut proposed = saved.clone();
+        proposed["server"]["port"] = json!(8080);
+        assert_eq!(
+            config.json_diff_from_disk(&proposed).unwrap(),
+            json!({"server": {"port": {"old": 80, "new": 8080}}})
+        );
+
+        proposed.as_object_mut().unwrap().remove("old");
+        proposed["new"] = json!(2);
+        assert_eq!(
+            config.json_diff_from_disk(&proposed).unwrap(),
+            json!({
+                "server": {"port": {"old": 80, "new": 8080}},
+                "new": {"old": null, "new": 2},
+                "old": {"old": 1, "new": null},
+            })
+        );
+
+        config.delete().unwrap();
+        assert_eq!(
+            config.json_diff_from_disk(&1).unwrap(),
+            json!({"old": null, "new": 1})
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_reload() {
+        use std::sync::{Arc, Mutex};
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "reload.json");
+        let mut people = People {
+            name: "a".to_string(),
+            age: 1,
+        };
+        config.save_json(&people).unwrap();
+        let shared = Arc::new(Mutex::new(People::default()));
+        config.json_reload_arc(&shared).unwrap();
+        assert_eq!(*shared.lock().unwrap(), people);
+
+        config
+            .save_json(People {
+                name: "b".to_string(),
+                age: 2,
+            })
+            .unwrap();
+        config.json_reload(&mut people).unwrap();
+        assert_eq!(people.name, "b");
+        config.json_reload_arc(&shared).unwrap();
+        assert_eq!(shared.lock().unwrap().age, 2);
+
+        config.save("{").unwrap();
+        assert!(config.json_reload(&mut people).is_err());
+        assert_eq!(people.name, "b");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_has_unsaved_changes() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "unsaved.json");
+        let mut data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_json(&data).unwrap();
+        assert!(!config.json_has_unsaved_changes(&data).unwrap());
+
+        data.age = 20;
+        assert!(config.json_has_unsaved_changes(&data).unwrap());
+
+        // Saving would drop the unknown key
+        data.age = 18;
+        config
+            .save(r#"{"name": "123", "age": 18, "extra": true}"#)
+            .unwrap();
+        assert_eq!(config.json::<People>().unwrap(), data);
+        assert!(config.json_has_unsaved_changes(&data).unwrap());
+
+        let value = 0.1f32;
+        config.save_json(value).unwrap();
+        assert!(!config.json_has_unsaved_changes(&value).unwrap());
+
+        config.delete().unwrap();
+        assert!(config.json_has_unsaved_changes(&data).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_layered_json() {
+        let _sandbox = sandbox();
+        let base = HomeConfig::with_config_dir("test", "layered.json");
+        let prod = base.with_name_suffix(".prod");
+        base.save(r#"{"name": "base", "age": 18, "db": {"host": "localhost", "port": 1}}"#)
+            .unwrap();
+        prod.save(r#"{"name": "prod", "db": {"port": 2}, "debug": false}"#)
+            .unwrap();
+
+        let value = HomeConfig::layered_json::<serde_json::Value>(&base, &prod).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "name": "prod",
+                "age": 18,
+                "db": {"host": "localhost", "port": 2},
+                "debug": false,
+            })
+        );
+
+        // Missing override
+        prod.delete().unwrap();
+        let people = HomeConfig::layered_json::<People>(&base, &prod).unwrap();
+        assert_eq!(people.name, "base");
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_layered_toml() {
+        let _sandbox = sandbox();
+        let base = HomeConfig::with_config_dir("test", "layered.toml");
+        let prod = base.with_name_suffix(".prod");
+        base.save("name = \"base\"\nage = 18\n[db]\nhost = \"localhost\"\nport = 1\n")
+            .unwrap();
+        prod.save("name = \"prod\"\n[db]\nport = 2\n").unwrap();
+
+        let value = HomeConfig::layered_toml::<toml::Value>(&base, &prod).unwrap();
+        assert_eq!(value["name"].as_str(), Some("prod"));
+        assert_eq!(value["age"].as_integer(), Some(18));
+        assert_eq!(value["db"]["host"].as_str(), Some("localhost"));
+        assert_eq!(value["db"]["port"].as_integer(), Some(2));
+
+        prod.delete().unwrap();
+        let people = HomeConfig::layered_toml::<People>(&base, &prod).unwrap();
+        assert_eq!(people.name, "base");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_set_json_defaults() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "defaults.json");
+        config
+            .save(r#"{"name": "user", "ui": {"theme": "light"}}"#)
+            .unwrap();
+        let defaults = serde_json::json!({
+            "name": "default",
+            "age": 30,
+            "ui": {"theme": "dark", "size": 12},
+        });
+        config.set_json_defaults(&defaults).unwrap();
+        assert_eq!(
+            config.json::<serde_json::Value>().unwrap(),
+            serde_json::json!({
+                "name": "user",
+                "age": 30,
+                "ui": {"theme": "light", "size": 12},
+            })
+        );
+
+        config.delete().unwrap();
+        config.set_json_defaults(&defaults).unwrap();
+        assert_eq!(config.json::<serde_json::Value>().unwrap(), defaults);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_parse_and_merge_json() {
+        let _sandbox = sandbox();
+        let system = HomeConfig::with_config_dir("test", "merge.system.json");
+        let user = HomeConfig::with_config_dir("test", "merge.user.json");
+        let local = HomeConfig::with_config_dir("test", "merge.local.json");
+        let missing = HomeConfig::with_config_dir("test", "merge.missing.json");
+        missing.delete().unwrap();
+
+        system
+            .save(r#"{"name": "system", "age": 1, "ui": {"theme": "dark", "size": 1}}"#)
+            .unwrap();
+        user.save(r#"{"email": "user@example.com", "ui": {"size": 2}}"#)
+            .unwrap();
+        local.save(r#"{"name": "local"}"#).unwrap();
+
+        let merged = HomeConfig::parse_and_merge_json::<serde_json::Value>(&[
+            &system, &missing, &user, &local,
+        ])
+        .unwrap();
+        assert_eq!(
+            merged,
+            serde_json::json!({
+                "name": "local",
+                "age": 1,
+                "email": "user@example.com",
+                "ui": {"theme": "dark", "size": 2},
+            })
+        );
+    }
+
+    #[test]
+    fn test_timestamp_suffix() {
+        let _sandbox = sandbox();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_705_314_600_123);
+        assert_eq!(timestamp(time), "2024-01-15T10-30-00.123Z");
+
+        let config = HomeConfig::with_config_dir("test", "timestamp.toml");
+        let a = config.with_timestamp_suffix();
+        thread::sleep(Duration::from_millis(2));
+        let b = config.with_timestamp_suffix();
+        assert_ne!(a.path(), b.path());
+        assert_eq!(a.path().parent(), config.path().parent());
+        assert_eq!(a.path().extension().unwrap(), "toml");
+
+        let saved = config.save_timestamped("123").unwrap();
+        assert_eq!(saved.read_to_string().unwrap(), "123");
+        saved.delete().unwrap();
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "bom.json");
+        config
+            .save(b"\xEF\xBB\xBF{\"name\": \"123\", \"age\": 18}")
+            .unwrap();
+        assert_eq!(
+            config.read_to_string_strip_bom().unwrap(),
+            r#"{"name": "123", "age": 18}"#
+        );
+        #[cfg(feature = "json")]
+        assert_eq!(config.json::<People>().unwrap().age, 18);
+
+        config.save("123").unwrap();
+        assert_eq!(config.read_to_string_strip_bom().unwrap(), "123");
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn test_base64() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "base64");
+        let data = (0..=255).collect::<Vec<u8>>();
+        config.save_base64(&data).unwrap();
+        assert!(config.read_to_string().unwrap().is_ascii());
+        assert_eq!(config.read_base64().unwrap(), data);
+
+        config.save("not base64!").unwrap();
+        let err = config.read_base64().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        #[cfg(feature = "json")]
+        {
+            config.save_base64(r#"{"name": "123", "age": 18}"#).unwrap();
+            assert_eq!(config.json_base64::<People>().unwrap().age, 18);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "keyring")]
+    fn test_keyring() {
+        let _sandbox = sandbox();
+        // The store is shared, use a service of our own
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let service = format!("home-config-test-{}-{}", std::process::id(), nanos);
+        let config = HomeConfig::with_file(Path::new(".config").join(service).join("keyring"));
+        if let Err(err) = config.save_keyring("123") {
+            let err = err.get_ref().and_then(|err| err.downcast_ref());
+            match err {
+                // No secret store available, eg. in a container
+                Some(keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_)) => {
+                    return
+                }
+                _ => panic!("Save to keyring: {:?}", err),
+            }
+        }
+        assert_eq!(config.read_keyring_to_string().unwrap(), "123");
+        assert!(!config.path().exists());
+
+        config.delete_keyring().unwrap();
+        let err = config.read_keyring_to_vec().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        config.delete_keyring().unwrap();
+    }
+
+    #[test]
+    fn test_save_returning_previous() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "previous");
+        config.delete().unwrap();
+        assert_eq!(config.save_returning_previous("1").unwrap(), None);
+        assert_eq!(
+            config.save_returning_previous("2").unwrap(),
+            Some(b"1".to_vec())
+        );
+        assert_eq!(config.read_to_string().unwrap(), "2");
+    }
+
+    #[test]
+    fn test_save_synced() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "synced");
+        config.delete().unwrap();
+        config.save_synced("123").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "123");
+        config.sync().unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(config.path(), fs::Permissions::from_mode(0o400)).unwrap();
+            config.sync().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_install_default() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "install_default");
+        config.delete().unwrap();
+
+        assert!(config.install_default("default").unwrap());
+        assert_eq!(config.read_to_string().unwrap(), "default");
+
+        assert!(!config.install_default("other").unwrap());
+        assert_eq!(config.read_to_string().unwrap(), "default");
+
+        // No temporary files are left behind
+        let dir = config.path().parent().unwrap();
+        let names = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["install_default"]);
+    }
+
+    #[test]
+    fn test_install_default_without_links() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "install_default_copy");
+        let tmp = HomeConfig::with_config_dir("test", "install_default_copy.tmp");
+        tmp.save("default").unwrap();
+
+        for kind in [ErrorKind::Unsupported, ErrorKind::PermissionDenied] {
+            config.delete().unwrap();
+            let linked = config.link_new(tmp.path(), |_, _| Err(kind.into()));
+            assert!(linked.unwrap());
+            assert_eq!(config.read_to_string().unwrap(), "default");
+
+            // Still never overwrites
+            config.save("other").unwrap();
+            let linked = config.link_new(tmp.path(), |_, _| Err(kind.into()));
+            assert!(!linked.unwrap());
+            assert_eq!(config.read_to_string().unwrap(), "other");
+        }
+
+        let linked = config.link_new(tmp.path(), |_, _| Err(ErrorKind::Other.into()));
+        assert_eq!(linked.unwrap_err().kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_install_default_json() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "install_default.json");
+        config.delete().unwrap();
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+
+        assert!(config.install_default_json(&data).unwrap());
+        assert!(!config.install_default_json(&People::default()).unwrap());
+        assert_eq!(config.json::<People>().unwrap(), data);
+    }
+
+    #[test]
+    fn test_touch() {
+        use std::time::{Duration, SystemTime};
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "touch");
+        config.delete().unwrap();
+
+        // Create
+        config.touch().unwrap();
+        assert_eq!(config.read_to_vec().unwrap(), b"");
+
+        // Update timestamp
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        config.save("123").unwrap();
+        File::options()
+            .write(true)
+            .open(config.path())
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        config.touch().unwrap();
+        let modified = config.path().metadata().unwrap().modified().unwrap();
+        assert!(modified > old);
+        assert_eq!(config.read_to_string().unwrap(), "123");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_save_secret() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _sandbox = sandbox();
+        let mode = |p: &Path| p.metadata().unwrap().permissions().mode() & 0o777;
+
+        let config = HomeConfig::with_config_dir("test-secret", "secret");
+        let _ = fs::remove_dir_all(config.path().parent().unwrap());
+
+        config.save_secret("token").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "token");
+        assert_eq!(mode(config.path()), 0o600);
+        assert_eq!(mode(config.path().parent().unwrap()), 0o700);
+
+        // Tighten existing file
+        fs::set_permissions(config.path(), fs::Permissions::from_mode(0o644)).unwrap();
+        config.save_secret("new token").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "new token");
+        assert_eq!(mode(config.path()), 0o600);
+
+        fs::remove_dir_all(config.path().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_rename() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "rename");
+        config.save("123").unwrap();
+
+        let renamed = config.rename("renamed").unwrap();
+        assert!(!config.path().exists());
+        assert_eq!(renamed.path(), &config.path().with_file_name("renamed"));
+        assert_eq!(renamed.read_to_string().unwrap(), "123");
+        renamed.delete().unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_create_hardlink() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "config.toml");
+        let current = HomeConfig::with_config_dir("test-link", "current.toml");
+        config.save("a = 1").unwrap();
+        assert_eq!(config.link_count().unwrap(), 1);
+
+        config.create_hardlink(&current).unwrap();
+        assert_eq!(config.link_count().unwrap(), 2);
+        assert_eq!(current.link_count().unwrap(), 2);
+        current.save("a = 2").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "a = 2");
+
+        let err = config.create_hardlink(&current).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(PathError::of(&err).unwrap().path(), current.path());
+        current.delete().unwrap();
+        assert_eq!(config.link_count().unwrap(), 1);
+        let err = current.link_count().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_secure() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test-secure", "secure");
+        config.save("token").unwrap();
+        let dir = config.path().parent().unwrap();
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700)).unwrap();
+        fs::set_permissions(config.path(), fs::Permissions::from_mode(0o644)).unwrap();
+
+        // Detect
+        let report = config.secure(false).unwrap();
+        assert_eq!(
+            report.findings,
+            vec![SecurityFinding {
+                path: config.path().clone(),
+                problem: SecurityProblem::FileAccessible { mode: 0o644 },
+                fixed: false,
+            }]
+        );
+        assert!(!report.is_secure());
+        let err = config.require_secure().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        // Repair
+        let report = config.secure(true).unwrap();
+        assert!(report.findings[0].fixed);
+        assert!(report.is_secure());
+        let mode = config.path().metadata().unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        assert!(config.secure(false).unwrap().findings.is_empty());
+        config.require_secure().unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exists_and_readable() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "readable");
+        let _ = config.delete();
+        assert!(!config.exists_and_readable());
+        assert!(!config.exists_and_writable());
+
+        config.save("123").unwrap();
+        assert!(config.exists_and_readable());
+        assert!(config.exists_and_writable());
+        assert!(!config.config_dir().unwrap().exists_and_writable());
+
+        // Permission bits do not apply to root
+        let root = config.path().metadata().unwrap().uid() == 0;
+        fs::set_permissions(config.path(), fs::Permissions::from_mode(0o200)).unwrap();
+        assert_eq!(config.exists_and_readable(), root);
+        assert!(config.exists_and_writable());
+        fs::set_permissions(config.path(), fs::Permissions::from_mode(0o400)).unwrap();
+        assert!(config.exists_and_readable());
+        assert_eq!(config.exists_and_writable(), root);
+        assert!(config.path().exists());
+        config.delete().unwrap();
+    }
+
+    #[test]
+    fn test_save_as() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test-save-as", "config.json");
+        let _ = config.delete_config_dir();
+        config.save_as("config.2024.json", "123").unwrap();
+        assert!(!config.path().exists());
+        let snapshot = config.path().with_file_name("config.2024.json");
+        assert_eq!(fs::read_to_string(snapshot).unwrap(), "123");
+
+        for name in ["", "..", "../config.json", "dir/config.json"] {
+            let err = config.save_as(name, "123").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_save_text() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "text");
+        config.save_text("123").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "123");
+        config.save_text(String::from("456")).unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "456");
+    }
+
+    #[test]
+    fn test_read_only() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "read_only");
+        config.save("123").unwrap();
+
+        let config = config.read_only();
+        assert!(config.display_path().ends_with("read_only"));
+        assert!(config.exists_and_readable());
+        assert!(config.canonical_path().unwrap().ends_with("read_only"));
+        assert!(config.same_file(&config.clone().into_writable()).unwrap());
+        assert_eq!(
+            config.path_hash(),
+            config.clone().into_writable().path_hash()
+        );
+        #[cfg(unix)]
+        assert_eq!(config.link_count().unwrap(), 1);
+        config.open_read().unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "123");
+        assert_eq!(config.read_to_string_opt().unwrap().unwrap(), "123");
+        assert_eq!(
+            config.read_to_string_lossy().unwrap(),
+            ("123".to_string(), false)
+        );
+        assert_eq!(config.read_to_string_strip_bom().unwrap(), "123");
+        let timeout = Duration::from_secs(5);
+        assert_eq!(config.read_to_string_timeout(timeout).unwrap(), "123");
+        let mut cache = ConfigCache::new();
+        let cached = config.read_to_string_cached(&mut cache).unwrap();
+        assert_eq!(cached.unwrap(), "123");
+        assert_eq!(config.read_to_vec().unwrap(), b"123");
+        assert_eq!(config.read_to_vec_opt().unwrap().unwrap(), b"123");
+
+        let config = config.into_writable();
+        config.save("456").unwrap();
+        assert_eq!(config.read_to_string().unwrap(), "456");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_read_only_json() {
+        use serde_json::{json, Value};
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "read_only.json");
+        config.save(r#"{"a":1}"#).unwrap();
+        let config = config.read_only();
+        let value = json!({"a": 1});
+
+        assert_eq!(config.json::<Value>().unwrap(), value);
+        assert_eq!(config.json_opt::<Value>().unwrap().unwrap(), value);
+        let reader = config.json_borrowed().unwrap();
+        assert_eq!(reader.parse::<Value>().unwrap(), value);
+        let seed = std::marker::PhantomData::<Value>;
+        assert_eq!(config.json_seeded(seed).unwrap(), value);
+        assert_eq!(config.json_with_comments::<Value>().unwrap(), value);
+        assert_eq!(config.json_with_includes::<Value>().unwrap(), value);
+        assert_eq!(config.json_lines::<Value>().unwrap(), vec![value.clone()]);
+        let lines = config.json_lines_iter::<Value>().unwrap();
+        assert_eq!(
+            lines.map(Result::unwrap).collect::<Vec<_>>(),
+            vec![value.clone()]
+        );
+        assert_eq!(config.json_as_flat_map().unwrap()["a"], 1);
+        assert!(!config.json_has_unsaved_changes(&value).unwrap());
+        assert!(config.json_diff_from_disk(&value).unwrap().is_null());
+        assert_eq!(config.as_value(Format::Json).unwrap(), value);
+        assert_eq!(
+            config.parse_lossy::<Value>(Format::Json).unwrap(),
+            (value.clone(), false)
+        );
+        let other = config.clone().into_writable();
+        assert!(config.semantic_eq(&other, Format::Json).unwrap());
+        #[cfg(feature = "gzip")]
+        assert_eq!(config.json_auto::<Value>().unwrap(), value);
+        #[cfg(feature = "lenient")]
+        {
+            assert_eq!(config.json_lenient::<Value>().unwrap().0, value);
+            let aliases = config.json_lenient_with_aliases::<Value>(&[("b", "a")]);
+            assert_eq!(aliases.unwrap().0, value);
+        }
+        #[cfg(feature = "schema")]
+        config.validate_schema(&json!({"type": "object"})).unwrap();
+        #[cfg(feature = "diagnostics")]
+        assert_eq!(
+            config.parse_diagnostic::<Value>(Format::Json).unwrap(),
+            value
+        );
+
+        let config = HomeConfig::with_config_dir("test", "read_only_array.json");
+        config.save("[1, 2]").unwrap();
+        let config = config.read_only();
+        let reader = config.json_array_reader::<u32>().unwrap();
+        assert_eq!(reader.map(Result::unwrap).collect::<Vec<_>>(), [1, 2]);
+
+        #[cfg(feature = "base64")]
+        {
+            let config = HomeConfig::with_config_dir("test", "read_only.b64");
+            config.save("eyJhIjoxfQ==").unwrap();
+            let config = config.read_only();
+            assert_eq!(config.read_base64().unwrap(), br#"{"a":1}"#);
+            assert_eq!(config.json_base64::<Value>().unwrap(), value);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml", feature = "toml", feature = "hcl"))]
+    fn test_read_only_formats() {
+        use std::collections::HashMap;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "read_only.yaml");
+        config.save("a: 1").unwrap();
+        let config = config.read_only();
+        let map = HashMap::from([("a".to_string(), 1)]);
+        assert_eq!(config.yaml::<HashMap<String, u32>>().unwrap(), map);
+        assert_eq!(
+            config.yaml_opt::<HashMap<String, u32>>().unwrap().unwrap(),
+            map
+        );
+
+        let config = HomeConfig::with_config_dir("test", "read_only.toml");
+        config.save("a = [1, 2]").unwrap();
+        let config = config.read_only();
+        let map = HashMap::from([("a".to_string(), vec![1, 2])]);
+        assert_eq!(config.toml::<HashMap<String, Vec<u32>>>().unwrap(), map);
+        assert_eq!(
+            config
+                .toml_opt::<HashMap<String, Vec<u32>>>()
+                .unwrap()
+                .unwrap(),
+            map
+        );
+        assert_eq!(config.toml_array_len("a").unwrap(), 2);
+
+        let config = HomeConfig::with_config_dir("test", "read_only.hcl");
+        config.save("a = 1").unwrap();
+        let config = config.read_only();
+        let map = HashMap::from([("a".to_string(), 1)]);
+        assert_eq!(config.hcl::<HashMap<String, u32>>().unwrap(), map);
+        assert_eq!(
+            config.hcl_opt::<HashMap<String, u32>>().unwrap().unwrap(),
+            map
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(all(
+        feature = "tokio",
+        feature = "json",
+        feature = "yaml",
+        feature = "toml",
+        feature = "hcl"
+    ))]
+    async fn test_read_only_async() {
+        use std::collections::HashMap;
+
+        let _sandbox = sandbox();
+        let map = HashMap::from([("a".to_string(), 1)]);
+        for (name, content) in [
+            ("json", r#"{"a":1}"#),
+            ("yaml", "a: 1"),
+            ("toml", "a = 1"),
+            ("hcl", "a = 1"),
+        ] {
+            let config = HomeConfig::with_config_dir("test", format!("read_only_async.{}", name));
+            config.save(content).unwrap();
+            let config = config.read_only();
+            assert_eq!(config.read_to_string_async().await.unwrap(), content);
+            assert_eq!(
+                config.read_to_vec_async().await.unwrap(),
+                content.as_bytes()
+            );
+            let parsed = match name {
+                "json" => config.json_async::<HashMap<String, u32>>().await.unwrap(),
+                "yaml" => config.yaml_async().await.unwrap(),
+                "toml" => config.toml_async().await.unwrap(),
+                _ => config.hcl_async().await.unwrap(),
+            };
+            assert_eq!(parsed, map);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn test_watch() {
+        use std::sync::mpsc;
+        use std::thread::sleep;
+        use std::time::Duration;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "watch");
+        config.delete().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let handle = config.watch(move |event| tx.send(event).unwrap()).unwrap();
+        let next = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        config.save("1").unwrap();
+        assert_eq!(next(), ChangeEvent::Created);
+        sleep(Duration::from_millis(200));
+
+        config.save("2").unwrap();
+        assert_eq!(next(), ChangeEvent::Modified);
+        sleep(Duration::from_millis(200));
+
+        // Replaced by rename, like vim does
+        let tmp = config.with_name_suffix(".swp");
+        tmp.save("3").unwrap();
+        fs::rename(tmp.path(), config.path()).unwrap();
+        assert_eq!(next(), ChangeEvent::Modified);
+        sleep(Duration::from_millis(200));
+
+        // Delete then recreate
+        config.delete().unwrap();
+        config.save("4").unwrap();
+        assert_eq!(next(), ChangeEvent::Recreated);
+        sleep(Duration::from_millis(200));
+
+        config.delete().unwrap();
+        assert_eq!(next(), ChangeEvent::Removed);
+
+        drop(handle);
+        sleep(Duration::from_millis(200));
+        config.save("5").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "watch", feature = "json"))]
+    fn test_watch_json() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "watch_json.json");
+        config.save_json(People::default()).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _handle = config
+            .watch_json(move |people: Result<People, JsonError>| tx.send(people).unwrap())
+            .unwrap();
+
+        let mut people = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_json(&people).unwrap();
+        people.age = 20;
+        config.save_json(&people).unwrap();
+        let parsed = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(parsed, people);
+        assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+
+        config.delete().unwrap();
+        let err = rx
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap_err();
+        assert!(err.is_not_found());
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn test_watch_dir() {
+        use std::thread::sleep;
+
+        let _sandbox = sandbox();
+        let settings = HomeConfig::with_config_dir("test-watch-dir", "settings.toml");
+        let theme = HomeConfig::with_config_dir("test-watch-dir", "theme.toml");
+        settings.delete_config_dir().unwrap();
+        settings.save("1").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _handle =
+            HomeConfig::watch_dir("test-watch-dir", move |event| tx.send(event).unwrap()).unwrap();
+        let next = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        settings.save("2").unwrap();
+        HomeConfig::with_config_dir("test-watch-dir", "settings.toml.tmp")
+            .save("2")
+            .unwrap();
+        assert_eq!(
+            next(),
+            DirChangeEvent {
+                name: "settings.toml".into(),
+                event: ChangeEvent::Modified,
+            }
+        );
+        sleep(Duration::from_millis(200));
+
+        // New files are picked up
+        theme.save("1").unwrap();
+        assert_eq!(
+            next(),
+            DirChangeEvent {
+                name: "theme.toml".into(),
+                event: ChangeEvent::Created,
+            }
+        );
+        assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+
+        assert!(is_temp_file(Path::new("config.toml.bak")));
+        assert!(is_temp_file(Path::new(".config.toml.swp")));
+        assert!(!is_temp_file(Path::new("config.toml")));
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn test_watch_channel() {
+        use std::time::Duration;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "watch_channel");
+        config.save("0").unwrap();
+
+        let (_handle, rx) = config.watch_channel(Duration::from_millis(250)).unwrap();
+        for i in 1..=5 {
+            config.save(i.to_string()).unwrap();
+        }
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            ChangeEvent::Modified
+        );
+        assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "watch", feature = "json"))]
+    fn test_reloading_config() {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "reloading.json");
+        let mut data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_json(&data).unwrap();
+
+        let (tx, errors) = mpsc::channel();
+        let settings = ReloadingConfig::with_on_error(
+            &config,
+            Duration::from_millis(50),
+            |config| config.json::<People>(),
+            move |err| tx.send(err).unwrap(),
+        )
+        .unwrap();
+        let updates = settings.subscribe();
+        assert_eq!(*settings.current(), data);
+
+        // Good edit
+        data.age = 20;
+        config.save_json(&data).unwrap();
+        let value = updates.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(*value, data);
+        assert_eq!(*settings.current(), data);
+
+        // Bad edit
+        config.save("{").unwrap();
+        let err = errors.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(err, JsonError::Serde(_)));
+        assert_eq!(*settings.current(), data);
+
+        // Deleted
+        config.delete().unwrap();
+        let err = errors.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(err, JsonError::Io(e) if e.kind() == ErrorKind::NotFound));
+        assert_eq!(*settings.current(), data);
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn test_content_async() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test-async", "file");
+        let _ = fs::remove_dir_all(config.path().parent().unwrap());
+        config.save_async("123").await.unwrap();
+        assert_eq!(config.read_to_string_async().await.unwrap(), "123");
+        assert_eq!(config.read_to_vec_async().await.unwrap(), b"123");
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tokio", feature = "json"))]
+    async fn test_json_async() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "async.json");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_json_async(&data).await.unwrap();
+        assert_eq!(config.json_async::<People>().await.unwrap(), data);
+
+        // Parsed on a blocking thread
+        let large = vec![data; 100_000];
+        config.save_json_async(&large).await.unwrap();
+        assert_eq!(config.json_async::<Vec<People>>().await.unwrap(), large);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tokio", feature = "yaml"))]
+    async fn test_yaml_async() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "async.yaml");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_yaml_async(&data).await.unwrap();
+        assert_eq!(config.yaml_async::<People>().await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tokio", feature = "toml"))]
+    async fn test_toml_async() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "async.toml");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_toml_async(&data).await.unwrap();
+        assert_eq!(config.toml_async::<People>().await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tokio", feature = "hcl"))]
+    async fn test_hcl_async() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "async.hcl");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_hcl_async(&data).await.unwrap();
+        assert_eq!(config.hcl_async::<People>().await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn test_lock_async() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "lock_async");
+        let lock = config.lock_exclusive_async().await.unwrap();
+
+        let other = config.clone();
+        let task = tokio::spawn(async move {
+            assert!(other.try_lock_exclusive_async().await.unwrap().is_none());
+            let timeout = Duration::from_millis(100);
+            assert!(tokio::time::timeout(timeout, other.lock_exclusive_async())
+                .await
+                .is_err());
+            other.lock_exclusive_async().await.unwrap()
+        });
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!task.is_finished());
+        drop(lock);
+        let lock = task.await.unwrap();
+        assert!(config.try_lock_exclusive_async().await.unwrap().is_none());
+        drop(lock);
+        assert!(config.try_lock_exclusive_async().await.unwrap().is_some());
     }
 
-    /// Delete the config file
-    pub fn delete(&self) -> IoResult<()> {
-        match fs::remove_file(&self.path) {
-            Ok(()) => Ok(()),
-            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
-            Err(err) => Err(err),
+    #[tokio::test(flavor = "multi_thread")]
+    #[cfg(all(feature = "tokio", feature = "json"))]
+    async fn test_modify_json_async() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "modify_async.json");
+        let tasks = (0..4)
+            .map(|_| {
+                let config = config.clone();
+                tokio::spawn(async move {
+                    for _ in 0..10 {
+                        config
+                            .modify_json_async(|count: &mut u32| *count += 1)
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
         }
+        assert_eq!(config.json::<u32>().unwrap(), 40);
+
+        let count = config
+            .modify_json_async(|count: &mut u32| {
+                *count += 1;
+                *count
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, 41);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::*;
+    #[test]
+    #[cfg(all(feature = "async-std", feature = "json"))]
+    fn test_async_std() {
+        let _sandbox = sandbox();
+        async_std::task::block_on(async {
+            let config = HomeConfig::with_config_dir("test-async-std", "config.json");
+            let _ = fs::remove_dir_all(config.path().parent().unwrap());
+            let data = People {
+                name: "123".to_string(),
+                age: 18,
+            };
+            config.save_json_async(&data).await.unwrap();
+            assert_eq!(config.json_async::<People>().await.unwrap(), data);
+
+            config.save_async("123").await.unwrap();
+            assert_eq!(config.read_to_string_async().await.unwrap(), "123");
+            assert_eq!(config.read_to_vec_async().await.unwrap(), b"123");
+
+            // Parsed on a blocking thread
+            let large = vec![data; 100_000];
+            config.save_json_async(&large).await.unwrap();
+            assert_eq!(config.json_async::<Vec<People>>().await.unwrap(), large);
+
+            let lock = config.lock_exclusive_async().await.unwrap();
+            assert!(config.try_lock_exclusive_async().await.unwrap().is_none());
+            drop(lock);
+            assert!(config.try_lock_exclusive_async().await.unwrap().is_some());
+        });
+    }
 
     #[test]
-    fn test_content() {
-        let config = HomeConfig::with_config_dir("test", "file");
-        // Save
+    fn test_delete_config_dir() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test-delete-dir", "config");
         config.save("123").unwrap();
-        // Read
-        assert_eq!(config.read_to_string().unwrap(), "123");
+        config.with_name_suffix(".bak").save("123").unwrap();
+        let dir = config.path().parent().unwrap();
+
+        let err = config.delete_config_dir_confirm(false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(dir.exists());
+
+        config.delete_config_dir_confirm(true).unwrap();
+        assert!(!dir.exists());
+        config.delete_config_dir().unwrap();
+
+        let config = HomeConfig::with_file("test-delete-dir.json");
+        config.save("123").unwrap();
+        let err = config.delete_config_dir().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(config.path().exists());
+        let err = HomeConfig::with_config_dir("test-delete-dir", "../../x.json")
+            .delete_config_dir()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let err = HomeConfig::from(home_dir().join(".config/x.json"))
+            .delete_config_dir()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(config.path().exists());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "watch-stream")]
+    async fn test_watch_stream() {
+        use std::time::Duration;
+        use tokio::time::timeout;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "watch_stream");
+        config.save("0").unwrap();
+
+        let mut changes = config.watch_stream(Duration::from_millis(50)).unwrap();
+        config.save("1").unwrap();
+        let event = timeout(Duration::from_secs(5), changes.next())
+            .await
+            .unwrap();
+        assert_eq!(event, Some(ChangeEvent::Modified));
+
+        config.delete().unwrap();
+        let event = timeout(Duration::from_secs(5), changes.next())
+            .await
+            .unwrap();
+        assert_eq!(event, Some(ChangeEvent::Removed));
     }
 
     #[test]
     fn test_delete() {
+        let _sandbox = sandbox();
         let config = HomeConfig::with_config_dir("test", "delete");
 
         assert!(!config.path().exists());
@@ -290,7 +5292,7 @@ mod tests {
     use serde::{Deserialize, Serialize};
 
     #[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
-    #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+    #[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
     struct People {
         name: String,
         age: u32,
@@ -299,6 +5301,7 @@ mod tests {
     #[test]
     #[cfg(feature = "json")]
     fn test_json() {
+        let _sandbox = sandbox();
         let config = HomeConfig::with_config_dir("test", "config.json");
         let data = People {
             name: "123".to_string(),
@@ -308,9 +5311,514 @@ mod tests {
         assert_eq!(config.json::<People>().unwrap(), data);
     }
 
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_to_json_string() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "to_string.json");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_json(&data).unwrap();
+        assert_eq!(
+            HomeConfig::to_json_string(&data).unwrap(),
+            config.read_to_string().unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_object() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "object.json");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_json_object(&data).unwrap();
+        assert_eq!(config.json::<People>().unwrap(), data);
+        // In the order of the fields
+        assert_eq!(
+            config.read_to_string().unwrap(),
+            HomeConfig::to_json_string(&data).unwrap()
+        );
+
+        assert!(matches!(
+            config.save_json_object(123),
+            Err(JsonError::Serde(_))
+        ));
+        assert_eq!(config.json::<People>().unwrap(), data);
+    }
+
+    #[test]
+    fn test_lock() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "lock");
+        let lock = config.lock_exclusive().unwrap();
+        assert!(config.try_lock_exclusive().unwrap().is_none());
+        drop(lock);
+        assert!(config.try_lock_exclusive().unwrap().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_modify_json() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "modify.json");
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..10 {
+                        config.modify_json(|count: &mut u32| *count += 1).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(config.json::<u32>().unwrap(), 40);
+
+        // Starts from the default for an empty file, a broken one is an error
+        config.save("").unwrap();
+        assert_eq!(config.modify_json(|v: &mut Vec<u32>| v.len()).unwrap(), 0);
+        config.save("[").unwrap();
+        let err = config.modify_json(|v: &mut Vec<u32>| v.len()).unwrap_err();
+        assert!(err.is_parse_error());
+        assert_eq!(config.read_to_string().unwrap(), "[");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_autosave() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "autosave.json");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        let file = ConfigFile::new(config.clone(), data.clone());
+        let settings = file.spawn_autosave(Duration::from_millis(50));
+
+        // Caught up by the background thread
+        settings.modify(|people| people.age = 20);
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(config.json::<People>().unwrap().age, 20);
+
+        settings.modify(|people| people.age = 21);
+        settings.flush_now().unwrap();
+        assert_eq!(config.json::<People>().unwrap().age, 21);
+
+        // Saved on drop
+        let settings = ConfigFile::<People>::load(config.clone())
+            .unwrap()
+            .spawn_autosave(Duration::from_secs(60));
+        settings.modify(|people| people.age = 22);
+        assert_eq!(settings.read(|people| people.age), 22);
+        drop(settings);
+        assert_eq!(config.json::<People>().unwrap().age, 22);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_autosave_on_error() {
+        let _sandbox = sandbox();
+        // The parent directory is a file, so every save fails
+        let parent = HomeConfig::with_config_dir("test", "autosave-blocked");
+        parent.save("").unwrap();
+        let config = HomeConfig::with_config_dir("test", "autosave-blocked/config.json");
+        let (tx, rx) = mpsc::channel();
+        let settings = ConfigFile::new(config, People::default())
+            .spawn_autosave_with_on_error(Duration::from_secs(60), move |err| {
+                tx.send(err).unwrap()
+            });
+
+        // Reported from the final save on drop
+        settings.modify(|people| people.age = 20);
+        drop(settings);
+        assert!(matches!(rx.try_recv().unwrap(), ConfigFileError::Io(_)));
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "json", feature = "tokio"))]
+    async fn test_autosave_task() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "autosave-task.json");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        let settings =
+            ConfigFile::new(config.clone(), data).spawn_autosave_task(Duration::from_millis(50));
+
+        settings.modify(|people| people.age = 20);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(config.json::<People>().unwrap().age, 20);
+
+        // Saved on drop
+        settings.modify(|people| people.age = 21);
+        drop(settings);
+        assert_eq!(config.json::<People>().unwrap().age, 21);
+        let dir = fs::read_dir(config.path().parent().unwrap()).unwrap();
+        assert!(!dir
+            .map(|entry| entry.unwrap().file_name())
+            .any(|name| name.to_string_lossy().ends_with(".tmp")));
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "json", feature = "tokio"))]
+    async fn test_autosave_task_on_error() {
+        let _sandbox = sandbox();
+        let parent = HomeConfig::with_config_dir("test", "autosave-task-blocked");
+        parent.save("").unwrap();
+        let config = HomeConfig::with_config_dir("test", "autosave-task-blocked/config.json");
+        let (tx, rx) = mpsc::channel();
+        let settings = ConfigFile::new(config, People::default())
+            .spawn_autosave_task_with_on_error(Duration::from_millis(50), move |err| {
+                let _ = tx.send(err);
+            });
+
+        settings.modify(|people| people.age = 20);
+        let err = tokio::task::spawn_blocking(move || rx.recv_timeout(Duration::from_secs(5)));
+        assert!(err.await.unwrap().is_ok());
+        drop(settings);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_file_conflict() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "conflict.json");
+        config
+            .save_json(People {
+                name: "123".to_string(),
+                age: 18,
+            })
+            .unwrap();
+
+        let mut file = ConfigFile::<People>::load(config.clone()).unwrap();
+        file.age = 20;
+        file.save_checked().unwrap();
+        assert_eq!(config.json::<People>().unwrap().age, 20);
+
+        // External modification
+        let theirs = People {
+            name: "456".to_string(),
+            age: 20,
+        };
+        config.save_json(&theirs).unwrap();
+        file.age = 30;
+        assert!(matches!(
+            file.save_checked(),
+            Err(ConfigFileError::Conflict {
+                theirs_changed_at: Some(_)
+            })
+        ));
+        assert_eq!(config.json::<People>().unwrap(), theirs);
+
+        // Merge: their name, our age
+        file.save_merged(MergeStrategy::Ours).unwrap();
+        let merged = People {
+            name: "456".to_string(),
+            age: 30,
+        };
+        assert_eq!(config.json::<People>().unwrap(), merged);
+        assert_eq!(*file, merged);
+
+        // Force
+        config.save_json(&theirs).unwrap();
+        file.name = "789".to_string();
+        file.save_force().unwrap();
+        assert_eq!(config.json::<People>().unwrap().name, "789");
+        file.save_checked().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_array_complete_write() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "array_large.json");
+        let item = People {
+            name: "x".repeat(1000),
+            age: 18,
+        };
+        let mut writer = config.json_array_writer().unwrap();
+        for _ in 0..1000 {
+            writer.push(&item).unwrap();
+        }
+        writer.finish_synced().unwrap();
+
+        // `[`, items separated by `,\n` after a leading `\n`, then `\n]\n`
+        let item_len = serde_json::to_string(&item).unwrap().len();
+        let expected = 1 + 1000 * (item_len + 2) - 1 + 3;
+        assert_eq!(fs::metadata(config.path()).unwrap().len(), expected as u64);
+        assert_eq!(config.json::<Vec<People>>().unwrap().len(), 1000);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_array() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "array.json");
+        let data = (0..1000)
+            .map(|i| People {
+                name: format!("name \"{}\" [{{,}}]", i),
+                age: i,
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = config.json_array_writer().unwrap();
+        for people in &data {
+            writer.push(people).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(config.json::<Vec<People>>().unwrap(), data);
+
+        let items = config
+            .json_array_reader::<People>()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(items, data);
+
+        config.save("[ 1, [2, 3] ,{\"a\": 4}, \"5\" ]").unwrap();
+        let items = config
+            .json_array_reader::<serde_json::Value>()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            serde_json::Value::from(items),
+            serde_json::json!([1, [2, 3], {"a": 4}, "5"])
+        );
+
+        config.json_array_writer().unwrap().finish().unwrap();
+        assert_eq!(config.json_array_reader::<People>().unwrap().count(), 0);
+
+        config.save("[1, 2").unwrap();
+        let mut reader = config.json_array_reader::<u32>().unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), 1);
+        assert_eq!(reader.next().unwrap().unwrap(), 2);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_seeded() {
+        use serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor};
+        use std::fmt;
+
+        struct Prefix<'a>(&'a str);
+
+        impl<'de> DeserializeSeed<'de> for Prefix<'_> {
+            type Value = Vec<String>;
+
+            fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+                d.deserialize_seq(self)
+            }
+        }
+
+        impl<'de> Visitor<'de> for Prefix<'_> {
+            type Value = Vec<String>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a list of strings")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut list = Vec::new();
+                while let Some(s) = seq.next_element::<String>()? {
+                    list.push(format!("{}{}", self.0, s));
+                }
+                Ok(list)
+            }
+        }
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "seeded.json");
+        config.save(r#"["a", "b"]"#).unwrap();
+        assert_eq!(
+            config.json_seeded(Prefix("app.")).unwrap(),
+            ["app.a", "app.b"]
+        );
+
+        // Buffering does not change error positions
+        config.save("[\n  \"a\",\n  1\n]").unwrap();
+        let seeded = Error::from(config.json_seeded(Prefix("")).unwrap_err());
+        let f = File::open(config.path()).unwrap();
+        let mut de = serde_json::Deserializer::from_reader(f);
+        let unbuffered = Error::from(Prefix("").deserialize(&mut de).unwrap_err());
+        assert_eq!(seeded.location(), unbuffered.location());
+    }
+
+    // Run with `cargo test --features json --release -- --ignored --nocapture bench_json_read`
+    #[test]
+    #[ignore]
+    #[cfg(feature = "json")]
+    fn bench_json_read() {
+        use std::time::Instant;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "bench.json");
+        let items = (0..100_000)
+            .map(|age| People {
+                name: format!("name {}", age),
+                age,
+            })
+            .collect::<Vec<_>>();
+        config.save_json(&items).unwrap();
+        let size = config.path().metadata().unwrap().len();
+
+        let time = |name: &str, f: &dyn Fn() -> Vec<People>| {
+            let start = Instant::now();
+            assert_eq!(f().len(), items.len());
+            println!("{}: {:?} for {} bytes", name, start.elapsed(), size);
+        };
+        time("unbuffered reader", &|| {
+            serde_json::from_reader(File::open(config.path()).unwrap()).unwrap()
+        });
+        time("buffered reader", &|| {
+            let f = std::io::BufReader::new(File::open(config.path()).unwrap());
+            serde_json::from_reader(f).unwrap()
+        });
+        time("read all, json()", &|| config.json().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_lines() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "lines.ndjson");
+        let data = (0..5)
+            .map(|i| People {
+                name: i.to_string(),
+                age: i,
+            })
+            .collect::<Vec<_>>();
+        config.save_json_lines(&data).unwrap();
+
+        let content = config.read_to_string().unwrap();
+        assert_eq!(content.lines().count(), 5);
+        for line in content.lines() {
+            serde_json::from_str::<People>(line).unwrap();
+        }
+
+        assert_eq!(config.json_lines::<People>().unwrap(), data);
+        let mut iter = config.json_lines_iter::<People>().unwrap();
+        assert_eq!(iter.next().unwrap().unwrap(), data[0]);
+        assert_eq!(iter.count(), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_borrowed() {
+        use std::borrow::Cow;
+
+        #[derive(Deserialize)]
+        struct Borrowed<'a> {
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+            #[serde(borrow)]
+            escaped: Cow<'a, str>,
+        }
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "borrowed.json");
+        config
+            .save(r#"{ "name": "XiaoMing", "escaped": "a\"b" }"#)
+            .unwrap();
+
+        let reader = config.json_borrowed().unwrap();
+        let data = reader.parse::<Borrowed>().unwrap();
+        assert!(matches!(data.name, Cow::Borrowed("XiaoMing")));
+        assert!(matches!(data.escaped, Cow::Owned(ref s) if s == "a\"b"));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_from_json_str() {
+        let people =
+            HomeConfig::from_json_str::<People>(r#"{ "name": "XiaoMing", "age": 18 }"#).unwrap();
+        assert_eq!(people.name, "XiaoMing");
+        assert_eq!(people.age, 18);
+        assert!(HomeConfig::from_json_str::<People>("{}").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_to_yaml_string() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "to_string.yaml");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_yaml(&data).unwrap();
+        assert_eq!(
+            HomeConfig::to_yaml_string(&data).unwrap(),
+            config.read_to_string().unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_to_toml_string() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "to_string.toml");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_toml(&data).unwrap();
+        assert_eq!(
+            HomeConfig::to_toml_string(&data).unwrap(),
+            config.read_to_string().unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", feature = "gzip"))]
+    fn test_json_auto() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let _sandbox = sandbox();
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+
+        let config = HomeConfig::with_config_dir("test", "auto.json");
+        config.save_json(&data).unwrap();
+        assert_eq!(config.json_auto::<People>().unwrap(), data);
+
+        let config = HomeConfig::with_config_dir("test", "auto.json.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(HomeConfig::to_json_string(&data).unwrap().as_bytes())
+            .unwrap();
+        config.save(encoder.finish().unwrap()).unwrap();
+        assert_eq!(config.json_auto::<People>().unwrap(), data);
+
+        // The decompressed content is limited too
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b' '; 4096]).unwrap();
+        config.save(encoder.finish().unwrap()).unwrap();
+        let err = config
+            .clone()
+            .with_max_size(1024)
+            .json_auto::<People>()
+            .unwrap_err();
+        assert_eq!(err.kind(), ConfigErrorKind::TooLarge);
+
+        // Only whitespace once decompressed
+        let err = config.json_auto::<People>().unwrap_err();
+        assert!(matches!(&err, JsonError::Empty(path) if path == config.path()));
+        assert!(err.is_empty_file());
+    }
+
     #[test]
     #[cfg(feature = "yaml")]
     fn test_yaml() {
+        let _sandbox = sandbox();
         let config = HomeConfig::with_config_dir("test", "config.yaml");
         let data = People {
             name: "123".to_string(),
@@ -320,9 +5828,58 @@ mod tests {
         assert_eq!(config.yaml::<People>().unwrap(), data);
     }
 
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_yaml_unrepresentable() {
+        use std::collections::HashMap;
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "keys.yaml");
+
+        // Integer keys are supported
+        let data = HashMap::from([(1u32, "a".to_string()), (2, "b".to_string())]);
+        config.save_yaml(&data).unwrap();
+        assert_eq!(config.yaml::<HashMap<u32, String>>().unwrap(), data);
+
+        #[derive(Serialize)]
+        enum Inner {
+            B(u32),
+        }
+
+        #[derive(Serialize)]
+        enum Outer {
+            A(Inner),
+        }
+
+        let err = match config.save_yaml(Outer::A(Inner::B(1))) {
+            Err(YamlError::Serde(err)) => err.to_string(),
+            _ => panic!("expected a serde error"),
+        };
+        assert!(err.starts_with(&format!(
+            "{}: value cannot be represented in yaml: ",
+            config.display_path()
+        )));
+        assert!(err.contains("nested enums"));
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_yaml_with_marker() {
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "marker.yaml");
+        let data = People {
+            name: "123".to_string(),
+            age: 18,
+        };
+        config.save_yaml_with_marker(&data).unwrap();
+        assert!(config.read_to_string().unwrap().starts_with("---\n"));
+        assert_eq!(config.yaml::<People>().unwrap(), data);
+    }
+
     #[test]
     #[cfg(feature = "toml")]
     fn test_toml() {
+        let _sandbox = sandbox();
         let config = HomeConfig::with_config_dir("test", "config.toml");
         let data = People {
             name: "123".to_string(),
@@ -332,9 +5889,162 @@ mod tests {
         assert_eq!(config.toml::<People>().unwrap(), data);
     }
 
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_save_toml_inline() {
+        use std::collections::BTreeMap;
+
+        let _sandbox = sandbox();
+        let data = toml::toml! {
+            name = "app"
+            [server]
+            host = "localhost"
+            port = 8080
+            [log.file]
+            path = "app.log"
+        };
+        let config = HomeConfig::with_config_dir("test", "inline.toml");
+
+        config.save_toml(&data).unwrap();
+        assert_eq!(
+            config.read_to_string().unwrap(),
+            "name = \"app\"\n\n[server]\nhost = \"localhost\"\nport = 8080\n\n[log.file]\npath = \"app.log\"\n"
+        );
+
+        config
+            .save_toml_inline(&data, &["server", "log.file", "missing"])
+            .unwrap();
+        assert_eq!(
+            config.read_to_string().unwrap(),
+            "name = \"app\"\nserver = { host = \"localhost\", port = 8080 }\n\n[log]\nfile = { path = \"app.log\" }\n"
+        );
+        let value = config.toml::<BTreeMap<String, toml::Value>>().unwrap();
+        assert_eq!(value.into_iter().collect::<toml::Table>(), data);
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_toml_array() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Items {
+            items: Vec<People>,
+        }
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "toml_array.toml");
+        let _ = config.delete();
+        assert!(config.toml_array_len("items").unwrap_err().is_not_found());
+
+        let a = People {
+            name: "a".to_string(),
+            age: 1,
+        };
+        let b = People {
+            name: "b".to_string(),
+            age: 2,
+        };
+        config.toml_array_append("items", &b).unwrap();
+        config.toml_array_prepend("items", &a).unwrap();
+        assert_eq!(config.toml_array_len("items").unwrap(), 2);
+        assert_eq!(config.toml_array_len("missing").unwrap(), 0);
+        assert!(config.read_to_string().unwrap().contains("[[items]]"));
+        assert_eq!(config.toml::<Items>().unwrap(), Items { items: vec![a, b] });
+
+        config.save("items = 1").unwrap();
+        assert!(config.toml_array_len("items").is_err());
+        let err = config.toml_array_append("items", 2).unwrap_err();
+        assert_eq!(err.kind(), ConfigErrorKind::Serialize);
+
+        // A broken file is reported as such, and left alone
+        config.save("items = [").unwrap();
+        let err = config.toml_array_append("items", 2).unwrap_err();
+        assert!(err.is_parse_error());
+        assert_eq!(err.path(), Some(config.path().as_path()));
+        assert!(err.location().is_some());
+        assert_eq!(config.read_to_string().unwrap(), "items = [");
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_toml_tables_before_values() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Settings {
+            owner: People,
+            tags: Vec<People>,
+            name: String,
+            port: u16,
+        }
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "table_order.toml");
+        let data = Settings {
+            owner: People {
+                name: "a".to_string(),
+                age: 1,
+            },
+            tags: vec![People {
+                name: "b".to_string(),
+                age: 2,
+            }],
+            name: "123".to_string(),
+            port: 8080,
+        };
+        config.save_toml(&data).unwrap();
+        assert_eq!(
+            config.read_to_string().unwrap(),
+            "name = \"123\"\nport = 8080\n\n[owner]\nname = \"a\"\nage = 1\n\n[[tags]]\nname = \"b\"\nage = 2\n"
+        );
+        assert_eq!(config.toml::<Settings>().unwrap(), data);
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_toml_array_of_tables() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Server {
+            host: String,
+            port: u16,
+            tags: Vec<String>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Servers {
+            servers: Vec<Server>,
+            name: String,
+            backup: Option<Server>,
+        }
+
+        let _sandbox = sandbox();
+        let config = HomeConfig::with_config_dir("test", "servers.toml");
+        let data = Servers {
+            servers: vec![
+                Server {
+                    host: "a".to_string(),
+                    port: 1,
+                    tags: vec!["x".to_string()],
+                },
+                Server {
+                    host: "b".to_string(),
+                    port: 2,
+                    tags: vec![],
+                },
+            ],
+            name: "123".to_string(),
+            backup: Some(Server {
+                host: "c".to_string(),
+                port: 3,
+                tags: vec![],
+            }),
+        };
+        config.save_toml(&data).unwrap();
+        assert!(config.read_to_string().unwrap().contains("[[servers]]"));
+        assert_eq!(config.toml::<Servers>().unwrap(), data);
+    }
+
     #[test]
     #[cfg(feature = "hcl")]
     fn test_hcl() {
+        let _sandbox = sandbox();
         let config = HomeConfig::with_config_dir("test", "config.hcl");
         let data = People {
             name: "123".to_string(),
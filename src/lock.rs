@@ -0,0 +1,94 @@
+use crate::HomeConfig;
+#[cfg(feature = "json")]
+use crate::JsonError;
+#[cfg(feature = "json")]
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Result as IoResult;
+
+/// An exclusive advisory lock on a config file, released on drop
+///
+/// The lock is held on a sibling `<file name>.lock` file, so it stays valid
+/// when the config itself is replaced. The lock file is not removed.
+#[derive(Debug)]
+pub struct ConfigLock {
+    file: File,
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+impl HomeConfig {
+    fn open_lock_file(&self) -> IoResult<File> {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        let lock = self.sibling(self.path.with_file_name(name));
+        lock.create_parent_dir()?;
+        lock.retry(|| {
+            OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&lock.path)
+        })
+    }
+
+    /// Acquire an exclusive lock on the config, blocking until it is available
+    ///
+    /// The lock is advisory: it only excludes other callers of the locking methods,
+    /// in this or any other process.
+    pub fn lock_exclusive(&self) -> IoResult<ConfigLock> {
+        let file = self.open_lock_file()?;
+        file.lock()?;
+        Ok(ConfigLock { file })
+    }
+
+    /// Acquire an exclusive lock on the config, or `None` if it is held elsewhere
+    pub fn try_lock_exclusive(&self) -> IoResult<Option<ConfigLock>> {
+        let file = self.open_lock_file()?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(ConfigLock { file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(err)) => Err(err),
+        }
+    }
+
+    /// Read, change and save the `json` config while holding the exclusive lock
+    ///
+    /// A missing or empty file starts from `T::default()`. Callers in this or other
+    /// processes do not lose each other's changes, and the file is replaced atomically,
+    /// see [`save_json_streaming`](Self::save_json_streaming).
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    /// use std::collections::HashMap;
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "counters.json");
+    /// let runs = config
+    ///     .modify_json(|counters: &mut HashMap<String, u64>| {
+    ///         let runs = counters.entry("runs".to_string()).or_default();
+    ///         *runs += 1;
+    ///         *runs
+    ///     })
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn modify_json<T, R, F>(&self, f: F) -> Result<R, JsonError>
+    where
+        T: Serialize + DeserializeOwned + Default,
+        F: FnOnce(&mut T) -> R,
+    {
+        let _lock = self.lock_exclusive()?;
+        let mut data = match self.json::<T>() {
+            Ok(data) => data,
+            Err(err) if err.is_not_found() || err.is_empty_file() => T::default(),
+            Err(err) => return Err(err),
+        };
+        let result = f(&mut data);
+        self.save_json_streaming(&data)?;
+        Ok(result)
+    }
+}
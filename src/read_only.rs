@@ -0,0 +1,209 @@
+#[cfg(feature = "hcl")]
+use crate::HclError;
+#[cfg(feature = "json")]
+use crate::JsonError;
+#[cfg(feature = "toml")]
+use crate::TomlParseError;
+#[cfg(feature = "yaml")]
+use crate::YamlError;
+use crate::{ConfigCache, HomeConfig, IoResult};
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+use crate::{Error, Format};
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Forward read methods to the inner config, generics are given in brackets
+macro_rules! forward {
+    ($(
+        $(#[$attr:meta])*
+        fn $name:ident $([$($generic:tt)*])? ($($arg:ident: $ty:ty),*) -> $ret:ty;
+    )*) => {$(
+        #[doc = concat!("See [`HomeConfig::", stringify!($name), "`]")]
+        $(#[$attr])*
+        pub fn $name $(<$($generic)*>)? (&self, $($arg: $ty),*) -> $ret {
+            self.inner.$name($($arg),*)
+        }
+    )*};
+    (async $(
+        $(#[$attr:meta])*
+        fn $name:ident $([$($generic:tt)*])? () -> $ret:ty;
+    )*) => {$(
+        #[doc = concat!("See [`HomeConfig::", stringify!($name), "`]")]
+        $(#[$attr])*
+        pub async fn $name $(<$($generic)*>)? (&self) -> $ret {
+            self.inner.$name().await
+        }
+    )*};
+}
+
+/// A [`HomeConfig`] that can only be read
+///
+/// Created by [`HomeConfig::read_only`], it has none of the mutating methods,
+/// so a stray write to a provisioned config does not compile:
+///
+/// ```compile_fail
+/// let config = home_config::HomeConfig::with_config_dir("app", "config").read_only();
+/// config.save("123").unwrap();
+/// ```
+///
+/// ```compile_fail
+/// let config = home_config::HomeConfig::with_config_dir("app", "config").read_only();
+/// config.save_secret("123").unwrap();
+/// ```
+///
+/// ```compile_fail
+/// let config = home_config::HomeConfig::with_config_dir("app", "config").read_only();
+/// config.install_default("123").unwrap();
+/// ```
+///
+/// ```compile_fail
+/// let config = home_config::HomeConfig::with_config_dir("app", "config").read_only();
+/// config.touch().unwrap();
+/// ```
+///
+/// ```compile_fail
+/// let config = home_config::HomeConfig::with_config_dir("app", "config").read_only();
+/// config.rename("other").unwrap();
+/// ```
+///
+/// ```compile_fail
+/// let config = home_config::HomeConfig::with_config_dir("app", "config").read_only();
+/// config.delete().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReadOnlyConfig {
+    inner: HomeConfig,
+}
+
+impl HomeConfig {
+    /// Turn into a config that rejects all mutations at compile time
+    pub fn read_only(self) -> ReadOnlyConfig {
+        ReadOnlyConfig { inner: self }
+    }
+}
+
+impl ReadOnlyConfig {
+    /// Get back a writable config
+    pub fn into_writable(self) -> HomeConfig {
+        self.inner
+    }
+
+    /// See [`HomeConfig::path`]
+    pub fn path(&self) -> &PathBuf {
+        self.inner.path()
+    }
+
+    forward! {
+        fn display_path() -> String;
+        fn exists_and_readable() -> bool;
+        fn canonical_path() -> IoResult<PathBuf>;
+        fn same_file(other: &HomeConfig) -> IoResult<bool>;
+        fn path_hash() -> u64;
+        #[cfg(unix)]
+        fn link_count() -> IoResult<u64>;
+        fn require_secure() -> IoResult<()>;
+        fn open_read() -> IoResult<File>;
+        fn read_to_string() -> IoResult<String>;
+        fn read_to_string_opt() -> IoResult<Option<String>>;
+        fn read_to_string_lossy() -> IoResult<(String, bool)>;
+        fn read_to_string_strip_bom() -> IoResult<String>;
+        fn read_to_string_timeout(dur: Duration) -> IoResult<String>;
+        fn read_to_string_cached(cache: &mut ConfigCache)
+            -> IoResult<Option<String>>;
+        fn read_to_vec() -> IoResult<Vec<u8>>;
+        fn read_to_vec_opt() -> IoResult<Option<Vec<u8>>>;
+        #[cfg(feature = "base64")]
+        fn read_base64() -> IoResult<Vec<u8>>;
+
+        #[cfg(feature = "json")]
+        fn json[T: DeserializeOwned]() -> Result<T, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_opt[T: DeserializeOwned]() -> Result<Option<T>, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_borrowed() -> IoResult<crate::JsonReader>;
+        #[cfg(feature = "json")]
+        fn json_seeded[S: serde::de::DeserializeSeed<'static>](seed: S)
+            -> Result<S::Value, JsonError>;
+        #[cfg(all(feature = "json", feature = "gzip"))]
+        fn json_auto[T: DeserializeOwned]() -> Result<T, JsonError>;
+        #[cfg(all(feature = "json", feature = "base64"))]
+        fn json_base64[T: DeserializeOwned]() -> Result<T, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_with_comments[T: DeserializeOwned]() -> Result<T, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_with_includes[T: DeserializeOwned]() -> Result<T, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_lines[T: DeserializeOwned]() -> Result<Vec<T>, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_lines_iter[T: DeserializeOwned]()
+            -> Result<impl Iterator<Item = Result<T, JsonError>>, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_array_reader[T: DeserializeOwned]()
+            -> Result<crate::JsonArrayReader<T>, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_as_flat_map()
+            -> Result<std::collections::BTreeMap<String, serde_json::Value>, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_diff_from_disk[T: serde::Serialize](proposed: &T)
+            -> Result<serde_json::Value, JsonError>;
+        #[cfg(feature = "json")]
+        fn json_has_unsaved_changes[T: serde::Serialize + DeserializeOwned + PartialEq](current: &T)
+            -> Result<bool, JsonError>;
+        #[cfg(feature = "lenient")]
+        fn json_lenient[T: DeserializeOwned]()
+            -> Result<(T, Vec<crate::Warning>), JsonError>;
+        #[cfg(feature = "lenient")]
+        fn json_lenient_with_aliases[T: DeserializeOwned](aliases: &[(&str, &str)])
+            -> Result<(T, Vec<crate::Warning>), JsonError>;
+        #[cfg(feature = "schema")]
+        fn validate_schema(schema: &serde_json::Value)
+            -> Result<(), Vec<crate::ValidationError>>;
+
+        #[cfg(feature = "yaml")]
+        fn yaml[T: DeserializeOwned]() -> Result<T, YamlError>;
+        #[cfg(feature = "yaml")]
+        fn yaml_opt[T: DeserializeOwned]() -> Result<Option<T>, YamlError>;
+        #[cfg(feature = "toml")]
+        fn toml[T: DeserializeOwned]() -> Result<T, TomlParseError>;
+        #[cfg(feature = "toml")]
+        fn toml_opt[T: DeserializeOwned]() -> Result<Option<T>, TomlParseError>;
+        #[cfg(feature = "toml")]
+        fn toml_array_len(key: &str) -> Result<usize, TomlParseError>;
+        #[cfg(feature = "hcl")]
+        fn hcl[T: DeserializeOwned]() -> Result<T, HclError>;
+        #[cfg(feature = "hcl")]
+        fn hcl_opt[T: DeserializeOwned]() -> Result<Option<T>, HclError>;
+
+        #[cfg(feature = "json")]
+        fn as_value(format: Format) -> Result<serde_json::Value, Error>;
+        #[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl"))]
+        fn parse_lossy[T: DeserializeOwned](format: Format) -> Result<(T, bool), Error>;
+        #[cfg(feature = "json")]
+        fn semantic_eq(other: &HomeConfig, format: Format) -> Result<bool, Error>;
+        #[cfg(all(
+            feature = "diagnostics",
+            any(feature = "json", feature = "yaml", feature = "toml", feature = "hcl")
+        ))]
+        fn parse_diagnostic[T: DeserializeOwned](format: Format)
+            -> Result<T, crate::ConfigDiagnostic>;
+    }
+
+    forward! {
+        async
+        #[cfg(any(feature = "tokio", feature = "async-std"))]
+        fn read_to_string_async() -> IoResult<String>;
+        #[cfg(any(feature = "tokio", feature = "async-std"))]
+        fn read_to_vec_async() -> IoResult<Vec<u8>>;
+        #[cfg(all(any(feature = "tokio", feature = "async-std"), feature = "json"))]
+        fn json_async[T: DeserializeOwned + Send + 'static]() -> Result<T, JsonError>;
+        #[cfg(all(any(feature = "tokio", feature = "async-std"), feature = "yaml"))]
+        fn yaml_async[T: DeserializeOwned + Send + 'static]() -> Result<T, YamlError>;
+        #[cfg(all(any(feature = "tokio", feature = "async-std"), feature = "toml"))]
+        fn toml_async[T: DeserializeOwned + Send + 'static]() -> Result<T, TomlParseError>;
+        #[cfg(all(any(feature = "tokio", feature = "async-std"), feature = "hcl"))]
+        fn hcl_async[T: DeserializeOwned + Send + 'static]() -> Result<T, HclError>;
+    }
+}
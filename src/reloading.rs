@@ -0,0 +1,159 @@
+use crate::{HomeConfig, WatchError, WatchHandle};
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// [`ReloadingConfig`] creation error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReloadError<E> {
+    Parse(E),
+    Watch(WatchError),
+}
+
+impl<E: fmt::Display> fmt::Display for ReloadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => err.fmt(f),
+            Self::Watch(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ReloadError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Parse(err) => Some(err),
+            Self::Watch(err) => Some(err),
+        }
+    }
+}
+
+type ErrorCallback<E> = Box<dyn FnMut(E) + Send>;
+
+/// A parsed config that is reloaded whenever the file changes
+///
+/// When the new content fails to parse, or the file is removed, the previous value is
+/// kept and the error is passed to the callback given to
+/// [`with_on_error`](Self::with_on_error). Stops reloading when dropped.
+///
+/// ```no_run
+/// use home_config::{HomeConfig, ReloadingConfig};
+/// use std::time::Duration;
+///
+/// let config = HomeConfig::with_config_dir("app", "config");
+/// let settings = ReloadingConfig::with_on_error(
+///     &config,
+///     Duration::from_millis(250),
+///     |config| config.read_to_string(),
+///     |err| eprintln!("Invalid config: {}", err),
+/// )
+/// .unwrap();
+///
+/// println!("{}", settings.current());
+/// ```
+pub struct ReloadingConfig<T, E> {
+    current: Arc<RwLock<Arc<T>>>,
+    subscribers: Arc<Mutex<Vec<Sender<Arc<T>>>>>,
+    on_error: Arc<Mutex<Option<ErrorCallback<E>>>>,
+    _handle: WatchHandle,
+}
+
+impl<T, E> ReloadingConfig<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + 'static,
+{
+    /// Parse the config with `parse` and watch it, see [`HomeConfig::watch_channel`]
+    ///
+    /// The errors of failed reloads are dropped until [`on_error`](Self::on_error) is
+    /// called, use [`with_on_error`](Self::with_on_error) to receive all of them.
+    pub fn new<P>(config: &HomeConfig, debounce: Duration, parse: P) -> Result<Self, ReloadError<E>>
+    where
+        P: Fn(&HomeConfig) -> Result<T, E> + Send + 'static,
+    {
+        Self::start(config, debounce, parse, None)
+    }
+
+    /// Like [`new`](Self::new), calling `on_error` with the errors of every failed reload
+    pub fn with_on_error<P, F>(
+        config: &HomeConfig,
+        debounce: Duration,
+        parse: P,
+        on_error: F,
+    ) -> Result<Self, ReloadError<E>>
+    where
+        P: Fn(&HomeConfig) -> Result<T, E> + Send + 'static,
+        F: FnMut(E) + Send + 'static,
+    {
+        Self::start(config, debounce, parse, Some(Box::new(on_error)))
+    }
+
+    fn start<P>(
+        config: &HomeConfig,
+        debounce: Duration,
+        parse: P,
+        on_error: Option<ErrorCallback<E>>,
+    ) -> Result<Self, ReloadError<E>>
+    where
+        P: Fn(&HomeConfig) -> Result<T, E> + Send + 'static,
+    {
+        let value = parse(config).map_err(ReloadError::Parse)?;
+        let current = Arc::new(RwLock::new(Arc::new(value)));
+        let subscribers = Arc::new(Mutex::new(Vec::<Sender<Arc<T>>>::new()));
+        let on_error = Arc::new(Mutex::new(on_error));
+
+        let handle = {
+            let watched = config.clone();
+            let current = current.clone();
+            let subscribers = subscribers.clone();
+            let on_error = on_error.clone();
+            config
+                .watch_with(debounce, move |_| match parse(&watched) {
+                    Ok(value) => {
+                        let value = Arc::new(value);
+                        *current.write().unwrap() = value.clone();
+                        subscribers
+                            .lock()
+                            .unwrap()
+                            .retain(|tx| tx.send(value.clone()).is_ok());
+                    }
+                    Err(err) => {
+                        if let Some(callback) = on_error.lock().unwrap().as_mut() {
+                            callback(err);
+                        }
+                    }
+                })
+                .map_err(ReloadError::Watch)?
+        };
+
+        Ok(Self {
+            current,
+            subscribers,
+            on_error,
+            _handle: handle,
+        })
+    }
+
+    /// Get the latest successfully parsed value
+    pub fn current(&self) -> Arc<T> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Receive every newly parsed value
+    pub fn subscribe(&self) -> Receiver<Arc<T>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Call `callback` with the errors of failed reloads, replacing the previous one
+    pub fn on_error<F>(&self, callback: F)
+    where
+        F: FnMut(E) + Send + 'static,
+    {
+        *self.on_error.lock().unwrap() = Some(Box::new(callback));
+    }
+}
@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::thread;
+use std::time::Duration;
+
+/// Retry policy for transient IO errors
+///
+/// Only `Interrupted`, `WouldBlock`, `TimedOut` and (on Windows) `PermissionDenied`
+/// are retried, parse errors are never retried.
+///
+/// ```no_run
+/// use home_config::{HomeConfig, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(5, Duration::from_millis(10), Duration::from_secs(1));
+/// let config = HomeConfig::with_config_dir("app", "config").with_retry(policy);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry, doubled on each following retry
+    pub base_delay: Duration,
+    /// Upper bound of the delay between two attempts
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a retry policy
+    pub fn new(attempts: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            attempts,
+            base_delay,
+            max_delay,
+        }
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    fn is_transient(err: &IoError) -> bool {
+        match err.kind() {
+            ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut => true,
+            ErrorKind::PermissionDenied => cfg!(windows),
+            _ => false,
+        }
+    }
+
+    /// Run `f` until it succeeds, fails with a non-transient error or runs out of attempts
+    ///
+    /// When more than one attempt was made, the final error wraps a [`RetryError`]
+    pub fn run<T, F>(&self, mut f: F) -> IoResult<T>
+    where
+        F: FnMut() -> IoResult<T>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Ok(val) => return Ok(val),
+                Err(err) if attempt < self.attempts && Self::is_transient(&err) => {
+                    thread::sleep(self.delay(attempt - 1));
+                    attempt += 1;
+                }
+                Err(err) if attempt > 1 => {
+                    return Err(IoError::new(
+                        err.kind(),
+                        RetryError {
+                            attempts: attempt,
+                            source: err,
+                        },
+                    ));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// The last error of a retried operation
+#[derive(Debug)]
+pub struct RetryError {
+    attempts: u32,
+    source: IoError,
+}
+
+impl RetryError {
+    /// Number of attempts made before giving up
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
+
+impl fmt::Display for RetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (after {} attempts)", self.source, self.attempts)
+    }
+}
+
+impl Error for RetryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -0,0 +1,78 @@
+use crate::HomeConfig;
+use serde_json::Value;
+use std::fmt;
+
+/// A violation found by [`HomeConfig::validate_schema`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    path: String,
+    message: String,
+}
+
+impl ValidationError {
+    fn root(message: impl fmt::Display) -> Self {
+        Self {
+            path: String::new(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Get the JSON pointer of the invalid value, eg. `/servers/2/port`, empty for the root
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.message),
+            path => write!(f, "{}: {}", path, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl HomeConfig {
+    /// Check the `json` config file against a JSON Schema, returning every violation
+    ///
+    /// A config file that cannot be read or parsed and an invalid `schema` are reported
+    /// as a single error for the root.
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    /// use serde_json::json;
+    ///
+    /// let schema = json!({"type": "object", "required": ["name"]});
+    /// let config = HomeConfig::with_config_dir("app", "config.json");
+    /// if let Err(errors) = config.validate_schema(&schema) {
+    ///     for error in errors {
+    ///         eprintln!("invalid config: {}", error);
+    ///     }
+    /// }
+    /// ```
+    pub fn validate_schema(&self, schema: &Value) -> Result<(), Vec<ValidationError>> {
+        let value = self
+            .json::<Value>()
+            .map_err(|err| vec![ValidationError::root(err)])?;
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|err| vec![ValidationError::root(format!("invalid schema: {}", err))])?;
+        let errors = validator
+            .iter_errors(&value)
+            .map(|err| ValidationError {
+                path: err.instance_path().to_string(),
+                message: err.to_string(),
+            })
+            .collect::<Vec<_>>();
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::HomeConfig;
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::path::PathBuf;
+
+/// A permission problem found by [`HomeConfig::secure`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityProblem {
+    /// The file is readable or writable by group or others
+    FileAccessible { mode: u32 },
+    /// The parent directory is writable by group or others
+    DirWritable { mode: u32 },
+    /// The file or directory is owned by another user
+    WrongOwner { uid: u32 },
+}
+
+impl fmt::Display for SecurityProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FileAccessible { mode } => {
+                write!(f, "accessible by group or others (mode {:o})", mode)
+            }
+            Self::DirWritable { mode } => {
+                write!(f, "writable by group or others (mode {:o})", mode)
+            }
+            Self::WrongOwner { uid } => write!(f, "owned by another user (uid {})", uid),
+        }
+    }
+}
+
+/// A single finding of a [`SecurityReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityFinding {
+    /// The file or directory the problem was found on
+    pub path: PathBuf,
+    /// What is wrong
+    pub problem: SecurityProblem,
+    /// Whether the problem has been repaired
+    pub fixed: bool,
+}
+
+impl fmt::Display for SecurityFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.problem)?;
+        if self.fixed {
+            write!(f, " (fixed)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of [`HomeConfig::secure`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityReport {
+    /// All problems found, in the order they were checked
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityReport {
+    /// No unrepaired problems remain
+    pub fn is_secure(&self) -> bool {
+        self.findings.iter().all(|finding| finding.fixed)
+    }
+}
+
+impl HomeConfig {
+    /// Audit the permissions and ownership of the config file and its parent directory
+    ///
+    /// Like `sshd` does for `~/.ssh`, the file must not be accessible by group or others,
+    /// the directory must not be writable by group or others, and both must be owned by the
+    /// current user. With `repair`, the offending mode bits are removed; ownership is never
+    /// changed.
+    ///
+    /// On Windows this is a no-op that returns an empty report.
+    pub fn secure(&self, repair: bool) -> IoResult<SecurityReport> {
+        let mut report = SecurityReport::default();
+        #[cfg(unix)]
+        {
+            use std::fs;
+            use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+            // SAFETY: `geteuid` is always successful and has no side effects
+            let uid = unsafe { libc::geteuid() };
+            let mut check =
+                |path: &std::path::Path, mask: u32, problem: fn(u32) -> SecurityProblem| {
+                    let meta = fs::metadata(path)?;
+                    if meta.uid() != uid {
+                        report.findings.push(SecurityFinding {
+                            path: path.to_path_buf(),
+                            problem: SecurityProblem::WrongOwner { uid: meta.uid() },
+                            fixed: false,
+                        });
+                    }
+                    let mode = meta.permissions().mode() & 0o777;
+                    if mode & mask != 0 {
+                        if repair {
+                            fs::set_permissions(path, fs::Permissions::from_mode(mode & !mask))?;
+                        }
+                        report.findings.push(SecurityFinding {
+                            path: path.to_path_buf(),
+                            problem: problem(mode),
+                            fixed: repair,
+                        });
+                    }
+                    IoResult::Ok(())
+                };
+
+            if let Some(parent) = self.path.parent() {
+                check(parent, 0o022, |mode| SecurityProblem::DirWritable { mode })?;
+            }
+            check(&self.path, 0o077, |mode| SecurityProblem::FileAccessible {
+                mode,
+            })?;
+        }
+        #[cfg(not(unix))]
+        let _ = repair;
+        Ok(report)
+    }
+
+    /// Fail with `PermissionDenied` if [`secure`](Self::secure) finds any problem
+    ///
+    /// Use this to refuse loading credentials from an insecure file
+    pub fn require_secure(&self) -> IoResult<()> {
+        let report = self.secure(false)?;
+        if report.is_secure() {
+            return Ok(());
+        }
+        let findings = report
+            .findings
+            .iter()
+            .map(|finding| finding.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(IoError::new(
+            ErrorKind::PermissionDenied,
+            format!("insecure config: {}", findings),
+        ))
+    }
+}
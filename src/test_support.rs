@@ -0,0 +1,72 @@
+//! Helpers for tests using configs, with the `test-support` feature
+//!
+//! ```toml
+//! [dev-dependencies]
+//! home-config = { version = "*", features = ["test-support"] }
+//! ```
+
+use crate::HOME_OVERRIDE;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub(crate) static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// A fresh home directory for the configs created on the current thread, see [`sandbox`]
+#[derive(Debug)]
+pub struct Sandbox {
+    home: PathBuf,
+    previous: Option<PathBuf>,
+}
+
+impl Sandbox {
+    /// Get the home directory of the sandbox
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+}
+
+/// Restores the previous home directory and deletes the sandbox
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        HOME_OVERRIDE.with(|h| *h.borrow_mut() = self.previous.take());
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+/// Use a fresh, empty home directory for the configs created on the current thread,
+/// until the guard is dropped
+///
+/// The override is thread local, like [`set_home_override`](crate::set_home_override),
+/// so tests running in parallel each get their own directory. Configs created on other
+/// threads, eg. in a spawned task, still use the real home directory; create them on the
+/// test thread. `$HOME` is not changed.
+///
+/// ```
+/// use home_config::test_support::sandbox;
+/// use home_config::HomeConfig;
+///
+/// let sandbox = sandbox();
+/// let config = HomeConfig::with_config_dir("app", "config");
+/// assert!(config.path().starts_with(sandbox.home()));
+/// config.save("123").unwrap();
+/// ```
+pub fn sandbox() -> Sandbox {
+    let home = loop {
+        let home = std::env::temp_dir().join(format!(
+            "home-config-sandbox-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        // Never reuse an existing directory, eg. left over by a killed process with the
+        // same id or created by someone else
+        match fs::create_dir(&home) {
+            Ok(()) => break home,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => panic!("Create sandbox home dir: {}", err),
+        }
+    };
+    let previous = HOME_OVERRIDE.with(|h| h.replace(Some(home.clone())));
+    Sandbox { home, previous }
+}
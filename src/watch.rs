@@ -0,0 +1,362 @@
+#[cfg(feature = "json")]
+use crate::JsonError;
+use crate::{home_dir, HomeConfig};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+// Editors emit several events per save, wait this long for the burst to end
+const SETTLE: Duration = Duration::from_millis(50);
+
+#[cfg(feature = "json")]
+const JSON_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// A change to a watched config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChangeEvent {
+    /// The file was created
+    Created,
+    /// The file was written, or replaced by an editor
+    Modified,
+    /// The file was removed
+    Removed,
+    /// The file was removed and created again
+    Recreated,
+}
+
+/// A change to a file in a watched config directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirChangeEvent {
+    /// The file name, relative to the directory
+    pub name: PathBuf,
+    /// The change
+    pub event: ChangeEvent,
+}
+
+/// Whether `name` looks like a temporary, backup, lock or editor swap file
+///
+/// eg. `config.tmp`, `config.bak`, `config~`, `.config.swp`, `config.lock`
+pub fn is_temp_file(name: &Path) -> bool {
+    let name = name.to_string_lossy();
+    const SUFFIXES: [&str; 7] = [".tmp", ".bak", ".swp", ".swo", ".swx", ".lock", "~"];
+    SUFFIXES.iter().any(|s| name.ends_with(s))
+        || name.starts_with(".#")
+        || (name.starts_with('#') && name.ends_with('#'))
+        // Written by vim to test if the directory is writable
+        || name == "4913"
+}
+
+/// Watch error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WatchError {
+    Io(IoError),
+    Notify(notify::Error),
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::Notify(err) => write!(f, "watch error: {}", err),
+        }
+    }
+}
+
+impl Error for WatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Notify(err) => Some(err),
+        }
+    }
+}
+
+impl From<IoError> for WatchError {
+    fn from(err: IoError) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<notify::Error> for WatchError {
+    fn from(err: notify::Error) -> Self {
+        Self::Notify(err)
+    }
+}
+
+/// Watches a config file until dropped
+#[derive(Debug)]
+pub struct WatchHandle {
+    _watcher: RecommendedWatcher,
+}
+
+impl HomeConfig {
+    /// Call `callback` whenever the config file changes
+    ///
+    /// The parent directory is watched, so editors that save by renaming a temporary file
+    /// are handled, and is created if missing. Bursts of file system events, like the several
+    /// writes of a single save, are reported as a single event.
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "config.toml");
+    /// let _handle = config.watch(|event| println!("{:?}", event)).unwrap();
+    /// ```
+    pub fn watch<F>(&self, callback: F) -> Result<WatchHandle, WatchError>
+    where
+        F: FnMut(ChangeEvent) + Send + 'static,
+    {
+        self.watch_with(SETTLE, callback)
+    }
+
+    /// Receive changes of the config file on a channel
+    ///
+    /// All events within `debounce` of each other are coalesced into one.
+    /// Otherwise the same as [`watch`](Self::watch).
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    /// use std::time::Duration;
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "config.toml");
+    /// let (_handle, rx) = config.watch_channel(Duration::from_millis(250)).unwrap();
+    /// for event in rx {
+    ///     println!("{:?}", event);
+    /// }
+    /// ```
+    pub fn watch_channel(
+        &self,
+        debounce: Duration,
+    ) -> Result<(WatchHandle, Receiver<ChangeEvent>), WatchError> {
+        let (tx, rx) = mpsc::channel();
+        let handle = self.watch_with(debounce, move |event| {
+            let _ = tx.send(event);
+        })?;
+        Ok((handle, rx))
+    }
+
+    /// Call `callback` with the parsed `json` content whenever the config file changes
+    ///
+    /// Changes within 100ms of each other are parsed once. A removed file is reported as
+    /// a `NotFound` error.
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "config.json");
+    /// let _handle = config
+    ///     .watch_json(|value: Result<serde_json::Value, _>| println!("{:?}", value))
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn watch_json<T, F>(&self, mut callback: F) -> Result<WatchHandle, WatchError>
+    where
+        T: DeserializeOwned,
+        F: FnMut(Result<T, JsonError>) + Send + 'static,
+    {
+        let config = self.clone();
+        self.watch_with(JSON_DEBOUNCE, move |_| callback(config.json()))
+    }
+
+    pub(crate) fn watch_with<F>(
+        &self,
+        debounce: Duration,
+        mut callback: F,
+    ) -> Result<WatchHandle, WatchError>
+    where
+        F: FnMut(ChangeEvent) + Send + 'static,
+    {
+        let name = PathBuf::from(self.path.file_name().unwrap_or_default());
+        let dir = self.path.parent().map(PathBuf::from).unwrap_or_default();
+        let filter = move |n: &Path| n == name;
+        watch_dir_with(dir, debounce, filter, move |event| callback(event.event))
+    }
+
+    /// Call `callback` whenever a file in the app config directory changes
+    ///
+    /// eg. for `~/.config/app/keybindings.toml` and `~/.config/app/theme.toml`.
+    /// New files are reported as [`ChangeEvent::Created`], changes are coalesced per file
+    /// like [`watch`](Self::watch), and temporary files are ignored, see [`is_temp_file`].
+    ///
+    /// ```no_run
+    /// use home_config::HomeConfig;
+    ///
+    /// let _handle = HomeConfig::watch_dir("app", |change| {
+    ///     println!("{}: {:?}", change.name.display(), change.event)
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn watch_dir<F>(app_name: &'static str, callback: F) -> Result<WatchHandle, WatchError>
+    where
+        F: FnMut(DirChangeEvent) + Send + 'static,
+    {
+        Self::watch_dir_filtered(app_name, |name| !is_temp_file(name), callback)
+    }
+
+    /// Same as [`watch_dir`](Self::watch_dir), reporting only the files accepted by `filter`
+    pub fn watch_dir_filtered<P, F>(
+        app_name: &'static str,
+        filter: P,
+        callback: F,
+    ) -> Result<WatchHandle, WatchError>
+    where
+        P: Fn(&Path) -> bool + Send + 'static,
+        F: FnMut(DirChangeEvent) + Send + 'static,
+    {
+        let dir = home_dir().join(".config").join(app_name);
+        watch_dir_with(dir, SETTLE, filter, callback)
+    }
+}
+
+fn watch_dir_with<P, F>(
+    dir: PathBuf,
+    debounce: Duration,
+    filter: P,
+    mut callback: F,
+) -> Result<WatchHandle, WatchError>
+where
+    P: Fn(&Path) -> bool + Send + 'static,
+    F: FnMut(DirChangeEvent) + Send + 'static,
+{
+    fs::create_dir_all(&dir)?;
+
+    let (tx, rx) = mpsc::channel::<(PathBuf, bool)>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(_) => return,
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        let removed = matches!(
+            event.kind,
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From))
+        );
+        for path in &event.paths {
+            if let Some(name) = path.file_name().map(PathBuf::from) {
+                if filter(&name) {
+                    let _ = tx.send((name, removed));
+                }
+            }
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    let mut existed = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| (PathBuf::from(entry.file_name()), true))
+        .collect::<HashMap<_, _>>();
+    thread::spawn(move || {
+        // Ends when the watcher, and with it the sender, is dropped
+        while let Ok(first) = rx.recv() {
+            // Changed names in order of first appearance, with whether they were removed
+            let mut burst = vec![first];
+            loop {
+                match rx.recv_timeout(debounce) {
+                    Ok((name, removed)) => match burst.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, r)) => *r |= removed,
+                        None => burst.push((name, removed)),
+                    },
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            for (name, removed) in burst {
+                let exists = dir.join(&name).exists();
+                let before = existed.insert(name.clone(), exists).unwrap_or(false);
+                let event = match (before, exists) {
+                    (false, true) => ChangeEvent::Created,
+                    (true, true) if removed => ChangeEvent::Recreated,
+                    (true, true) => ChangeEvent::Modified,
+                    (true, false) => ChangeEvent::Removed,
+                    (false, false) => continue,
+                };
+                callback(DirChangeEvent { name, event });
+            }
+        }
+    });
+
+    Ok(WatchHandle { _watcher: watcher })
+}
+
+#[cfg(feature = "watch-stream")]
+const STREAM_CAPACITY: usize = 16;
+
+/// A [`Stream`](futures_core::Stream) of config file changes, created by
+/// [`HomeConfig::watch_stream`]
+///
+/// Stops watching when dropped.
+#[cfg(feature = "watch-stream")]
+#[derive(Debug)]
+pub struct WatchStream {
+    rx: futures_channel::mpsc::Receiver<ChangeEvent>,
+    _handle: WatchHandle,
+}
+
+#[cfg(feature = "watch-stream")]
+impl WatchStream {
+    /// Wait for the next change
+    pub async fn next(&mut self) -> Option<ChangeEvent> {
+        std::future::poll_fn(|cx| {
+            futures_core::Stream::poll_next(std::pin::Pin::new(&mut self.rx), cx)
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "watch-stream")]
+impl futures_core::Stream for WatchStream {
+    type Item = ChangeEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<ChangeEvent>> {
+        std::pin::Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
+
+#[cfg(feature = "watch-stream")]
+impl HomeConfig {
+    /// Receive changes of the config file as an async stream
+    ///
+    /// Events are coalesced like [`watch_channel`](Self::watch_channel). At most 16 events
+    /// are buffered, newer events are dropped while the buffer is full; as every event
+    /// means "read the file again", no change is missed by the reader.
+    ///
+    /// ```no_run
+    /// # async fn run() {
+    /// use home_config::HomeConfig;
+    /// use std::time::Duration;
+    ///
+    /// let config = HomeConfig::with_config_dir("app", "config.json");
+    /// let mut changes = config.watch_stream(Duration::from_millis(250)).unwrap();
+    /// while changes.next().await.is_some() {
+    ///     let settings = config.read_to_string();
+    /// }
+    /// # }
+    /// ```
+    pub fn watch_stream(&self, debounce: Duration) -> Result<WatchStream, WatchError> {
+        let (mut tx, rx) = futures_channel::mpsc::channel(STREAM_CAPACITY);
+        let handle = self.watch_with(debounce, move |event| {
+            let _ = tx.try_send(event);
+        })?;
+        Ok(WatchStream {
+            rx,
+            _handle: handle,
+        })
+    }
+}